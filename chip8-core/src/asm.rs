@@ -0,0 +1,571 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    disasm::instruction::Instruction,
+    emulator::consts::{PROGRAM_START_ADDRESS, XO_CHIP_MEMORY_SIZE},
+};
+
+// How deep includes and defines may nest, which catches files including
+// themselves and defines referring to themselves.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl core::error::Error for AsmError {}
+
+pub struct Assembly {
+    // The ROM image, to be loaded at 0x200.
+    pub rom: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    // One `0x0200 name` line per label, sorted by address.
+    pub fn symbol_file(&self) -> String {
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|&(name, address)| (*address, name));
+
+        labels
+            .into_iter()
+            .map(|(name, address)| format!("0x{address:04X} {name}\n"))
+            .collect()
+    }
+}
+
+// A line of source, after includes are expanded.
+struct Line {
+    file: String,
+    number: usize,
+    text: String,
+}
+
+enum Symbol {
+    Label(u16),
+    // Defines are kept as written and evaluated where they are used, so they may refer to labels.
+    Define(String),
+}
+
+// Assembles `source`, a program written with the classic mnemonics of Cowgod's
+// reference (the ones `disasm --syntax classic` prints):
+//
+//     define SPEED 2            ; or: SPEED equ 2
+//     loop:
+//         LD V0, SPEED
+//         CALL draw
+//         JP loop
+//     include "draw.asm"
+//     sprite:
+//         DB 0xF0, 0x90, %11110000
+//
+// `include` gets the contents of other files through `read_file`. Their paths
+// are relative to the file the `include` is in, `file_name` being the path of
+// `source`, and are given to `read_file` already joined to it.
+pub fn assemble(
+    source: &str,
+    file_name: &str,
+    read_file: &mut dyn FnMut(&str) -> Result<String, String>,
+) -> Result<Assembly, AsmError> {
+    let mut lines = vec![];
+    expand_includes(source, file_name, read_file, 0, &mut lines)?;
+
+    // The first pass only finds where labels are, the second one emits the code.
+    let mut symbols = BTreeMap::new();
+    let mut rom = vec![];
+    for final_pass in [false, true] {
+        rom.clear();
+
+        for line in &lines {
+            let error = |message: String| AsmError {
+                file: line.file.clone(),
+                line: line.number,
+                message,
+            };
+            let address = PROGRAM_START_ADDRESS + rom.len();
+
+            let statement = match parse_label(&line.text) {
+                (Some(label), statement) => {
+                    if !final_pass
+                        && symbols
+                            .insert(label.to_string(), Symbol::Label(address as u16))
+                            .is_some()
+                    {
+                        return Err(error(format!("Duplicate symbol: {label}")));
+                    }
+                    statement
+                }
+                (None, statement) => statement,
+            };
+
+            let assembler = Assembler {
+                symbols: &symbols,
+                final_pass,
+            };
+            match assembler.statement(statement, &mut rom) {
+                Ok(Some((name, value))) if !final_pass => {
+                    if symbols.contains_key(&name) {
+                        return Err(error(format!("Duplicate symbol: {name}")));
+                    }
+                    symbols.insert(name, Symbol::Define(value));
+                }
+                Ok(_) => {}
+                Err(message) => return Err(error(message)),
+            }
+
+            if PROGRAM_START_ADDRESS + rom.len() > XO_CHIP_MEMORY_SIZE {
+                return Err(error(String::from("Program doesn't fit in memory")));
+            }
+        }
+    }
+
+    let labels = symbols
+        .into_iter()
+        .filter_map(|(name, symbol)| match symbol {
+            Symbol::Label(address) => Some((name, address)),
+            Symbol::Define(_) => None,
+        })
+        .collect();
+
+    Ok(Assembly { rom, labels })
+}
+
+fn expand_includes(
+    source: &str,
+    file_name: &str,
+    read_file: &mut dyn FnMut(&str) -> Result<String, String>,
+    depth: usize,
+    lines: &mut Vec<Line>,
+) -> Result<(), AsmError> {
+    for (i, text) in source.lines().enumerate() {
+        let text = strip_comment(text);
+        let error = |message: String| AsmError {
+            file: file_name.to_string(),
+            line: i + 1,
+            message,
+        };
+
+        let mut words = text.split_whitespace();
+        if words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case("include"))
+        {
+            let path = text.trim()[7..].trim().trim_matches('"');
+            if depth >= MAX_DEPTH {
+                return Err(error(format!("Includes nested too deep at {path}")));
+            }
+
+            let path = include_path(file_name, path);
+            let included = read_file(&path).map_err(|err| error(format!("{path}: {err}")))?;
+            expand_includes(&included, &path, read_file, depth + 1, lines)?;
+        } else {
+            lines.push(Line {
+                file: file_name.to_string(),
+                number: i + 1,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// `path` as seen from the directory of `file_name`, unless it is absolute.
+fn include_path(file_name: &str, path: &str) -> String {
+    let absolute = path.starts_with(['/', '\\']) || path.get(1..2) == Some(":");
+    match file_name.rfind(['/', '\\']) {
+        Some(end) if !absolute => format!("{}{path}", &file_name[..=end]),
+        _ => path.to_string(),
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.split(';').next().unwrap_or_default()
+}
+
+// Splits `label: statement` into its two parts.
+fn parse_label(text: &str) -> (Option<&str>, &str) {
+    let text = text.trim();
+    match text.split_once(':') {
+        Some((label, statement)) if is_identifier(label) => (Some(label), statement.trim()),
+        _ => (None, text),
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+struct Assembler<'a> {
+    symbols: &'a BTreeMap<String, Symbol>,
+    // Before the final pass, symbols that aren't known yet read as 0.
+    final_pass: bool,
+}
+
+impl Assembler<'_> {
+    // Emits the bytes of a statement, or returns the name and value of a define.
+    fn statement(
+        &self,
+        statement: &str,
+        rom: &mut Vec<u8>,
+    ) -> Result<Option<(String, String)>, String> {
+        let (mnemonic, rest) = match statement.split_once(char::is_whitespace) {
+            Some((mnemonic, rest)) => (mnemonic, rest.trim()),
+            None => (statement, ""),
+        };
+        if mnemonic.is_empty() {
+            return Ok(None);
+        }
+
+        // `NAME equ VALUE`
+        if let Some((keyword, value)) = rest.split_once(char::is_whitespace)
+            && keyword.eq_ignore_ascii_case("equ")
+        {
+            return self.define(mnemonic, value).map(Some);
+        }
+
+        let operands: Vec<&str> = if rest.is_empty() {
+            vec![]
+        } else {
+            rest.split(',').map(str::trim).collect()
+        };
+
+        match mnemonic.to_ascii_uppercase().as_str() {
+            "DEFINE" => {
+                let (name, value) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or("Expected: define NAME VALUE")?;
+                return self.define(name, value).map(Some);
+            }
+            "DB" => {
+                for operand in operands {
+                    rom.push(self.byte(operand)?);
+                }
+            }
+            "DW" => {
+                for operand in operands {
+                    rom.extend(self.value(operand)?.to_be_bytes());
+                }
+            }
+            mnemonic => self.instruction(mnemonic, &operands)?.encode(rom),
+        }
+
+        Ok(None)
+    }
+
+    fn define(&self, name: &str, value: &str) -> Result<(String, String), String> {
+        if !is_identifier(name) {
+            return Err(format!("Invalid symbol name: {name}"));
+        }
+        Ok((name.to_string(), value.trim().to_string()))
+    }
+
+    fn instruction(&self, mnemonic: &str, operands: &[&str]) -> Result<Instruction, String> {
+        let upper: Vec<String> = operands.iter().map(|op| op.to_ascii_uppercase()).collect();
+        let upper: Vec<&str> = upper.iter().map(String::as_str).collect();
+        let v = |i: usize| register(operands[i]);
+
+        let instruction = match (mnemonic, upper.as_slice()) {
+            ("CLS", []) => Instruction::Clear,
+            ("RET", []) => Instruction::Return,
+            ("SCD", [_]) => Instruction::ScrollDown {
+                n: self.nibble(operands[0])?,
+            },
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::LowRes,
+            ("HIGH", []) => Instruction::HighRes,
+            ("SYS", [_]) => Instruction::MachineCall {
+                address: self.address(operands[0])?,
+            },
+            ("JP", ["V0", _]) => Instruction::JumpOffset {
+                x: 0,
+                address: self.address(operands[1])?,
+            },
+            ("JP", [_]) => Instruction::Jump {
+                address: self.address(operands[0])?,
+            },
+            ("CALL", [_]) => Instruction::Call {
+                address: self.address(operands[0])?,
+            },
+            ("SE", [_, _]) => match v(1) {
+                Some(y) => Instruction::SkipIfRegistersEqual {
+                    x: self.v(operands[0])?,
+                    y,
+                },
+                None => Instruction::SkipIfEqual {
+                    x: self.v(operands[0])?,
+                    byte: self.byte(operands[1])?,
+                },
+            },
+            ("SNE", [_, _]) => match v(1) {
+                Some(y) => Instruction::SkipIfRegistersNotEqual {
+                    x: self.v(operands[0])?,
+                    y,
+                },
+                None => Instruction::SkipIfNotEqual {
+                    x: self.v(operands[0])?,
+                    byte: self.byte(operands[1])?,
+                },
+            },
+            ("SAVE", [_, _]) => Instruction::SaveRange {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("LOAD", [_, _]) => Instruction::LoadRange {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("LD", ["I", long]) if long.starts_with("LONG ") => Instruction::SetIndexLong {
+                address: self.value(operands[1][5..].trim())?,
+            },
+            ("LD", ["I", _]) => Instruction::SetIndex {
+                address: self.address(operands[1])?,
+            },
+            ("LD", [_, "DT"]) => Instruction::GetDelay {
+                x: self.v(operands[0])?,
+            },
+            ("LD", [_, "K"]) => Instruction::WaitKey {
+                x: self.v(operands[0])?,
+            },
+            ("LD", ["DT", _]) => Instruction::SetDelay {
+                x: self.v(operands[1])?,
+            },
+            ("LD", ["ST", _]) => Instruction::SetSound {
+                x: self.v(operands[1])?,
+            },
+            ("LD", ["F", _]) => Instruction::Font {
+                x: self.v(operands[1])?,
+            },
+            ("LD", ["HF", _]) => Instruction::BigFont {
+                x: self.v(operands[1])?,
+            },
+            ("LD", ["B", _]) => Instruction::Bcd {
+                x: self.v(operands[1])?,
+            },
+            ("LD", ["[I]", _]) => Instruction::Save {
+                x: self.v(operands[1])?,
+            },
+            ("LD", [_, "[I]"]) => Instruction::Load {
+                x: self.v(operands[0])?,
+            },
+            ("LD", ["R", _]) => Instruction::SaveFlags {
+                x: self.v(operands[1])?,
+            },
+            ("LD", [_, "R"]) => Instruction::LoadFlags {
+                x: self.v(operands[0])?,
+            },
+            ("LD", [_, _]) => match v(1) {
+                Some(y) => Instruction::Move {
+                    x: self.v(operands[0])?,
+                    y,
+                },
+                None => Instruction::Set {
+                    x: self.v(operands[0])?,
+                    byte: self.byte(operands[1])?,
+                },
+            },
+            ("ADD", ["I", _]) => Instruction::AddIndex {
+                x: self.v(operands[1])?,
+            },
+            ("ADD", [_, _]) => match v(1) {
+                Some(y) => Instruction::AddRegisters {
+                    x: self.v(operands[0])?,
+                    y,
+                },
+                None => Instruction::Add {
+                    x: self.v(operands[0])?,
+                    byte: self.byte(operands[1])?,
+                },
+            },
+            ("OR", [_, _]) => Instruction::Or {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("AND", [_, _]) => Instruction::And {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("XOR", [_, _]) => Instruction::Xor {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("SUB", [_, _]) => Instruction::Sub {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            ("SUBN", [_, _]) => Instruction::SubReversed {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+            },
+            // `SHR Vx` is short for `SHR Vx, Vx`.
+            ("SHR", [_] | [_, _]) => Instruction::ShiftRight {
+                x: self.v(operands[0])?,
+                y: self.v(operands.last().unwrap_or(&operands[0]))?,
+            },
+            ("SHL", [_] | [_, _]) => Instruction::ShiftLeft {
+                x: self.v(operands[0])?,
+                y: self.v(operands.last().unwrap_or(&operands[0]))?,
+            },
+            ("RND", [_, _]) => Instruction::Random {
+                x: self.v(operands[0])?,
+                byte: self.byte(operands[1])?,
+            },
+            ("DRW", [_, _, _]) => Instruction::Draw {
+                x: self.v(operands[0])?,
+                y: self.v(operands[1])?,
+                n: self.nibble(operands[2])?,
+            },
+            ("SKP", [_]) => Instruction::SkipIfKey {
+                x: self.v(operands[0])?,
+            },
+            ("SKNP", [_]) => Instruction::SkipIfNotKey {
+                x: self.v(operands[0])?,
+            },
+            ("PLANE", [_]) => Instruction::SelectPlanes {
+                n: self.nibble(operands[0])?,
+            },
+            ("AUDIO", []) => Instruction::LoadAudio,
+            ("PITCH", [_]) => Instruction::SetPitch {
+                x: self.v(operands[0])?,
+            },
+            _ => {
+                return Err(format!(
+                    "Unknown instruction: {mnemonic} {}",
+                    operands.join(", ")
+                ));
+            }
+        };
+
+        Ok(instruction)
+    }
+
+    fn v(&self, operand: &str) -> Result<u8, String> {
+        register(operand).ok_or(format!("Expected a register, found {operand}"))
+    }
+
+    fn nibble(&self, operand: &str) -> Result<u8, String> {
+        match self.value(operand)? {
+            value @ 0..=0xF => Ok(value as u8),
+            value => Err(format!("Value doesn't fit in 4 bits: {value}")),
+        }
+    }
+
+    // Negative bytes down to -128 are stored in two's complement, so `ADD V0, -1` subtracts 1.
+    fn byte(&self, operand: &str) -> Result<u8, String> {
+        match self.signed_value(operand)? {
+            value @ -0x80..=0xFF => Ok(value as u8),
+            value => Err(format!("Value doesn't fit in a byte: {value}")),
+        }
+    }
+
+    fn address(&self, operand: &str) -> Result<u16, String> {
+        match self.value(operand)? {
+            value @ 0..=0xFFF => Ok(value),
+            value => Err(format!("Address doesn't fit in 12 bits: 0x{value:X}")),
+        }
+    }
+
+    fn value(&self, operand: &str) -> Result<u16, String> {
+        let value = self.signed_value(operand)?;
+        u16::try_from(value).map_err(|_| format!("Value out of range: {value}"))
+    }
+
+    // Sums and differences of numbers and symbols, such as `sprite + 5` or `-2`.
+    fn signed_value(&self, expression: &str) -> Result<i64, String> {
+        self.evaluate(expression, 0)
+    }
+
+    fn evaluate(&self, expression: &str, depth: usize) -> Result<i64, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("Define refers to itself: {expression}"));
+        }
+
+        if expression.trim().is_empty() {
+            return Err(String::from("Missing value"));
+        }
+
+        let mut total: i64 = 0;
+        let mut sign = 1;
+        let mut term = String::new();
+
+        // The `+` at the end adds up the last term.
+        for c in expression.chars().chain(['+']) {
+            match c {
+                '+' | '-' if !term.trim().is_empty() => {
+                    total = self
+                        .term(term.trim(), depth)?
+                        .checked_mul(sign)
+                        .and_then(|term| total.checked_add(term))
+                        .ok_or(format!("Value out of range: {}", expression.trim()))?;
+                    term.clear();
+                    sign = if c == '-' { -1 } else { 1 };
+                }
+                // Signs in front of a term, as in `-2`.
+                '-' => sign = -sign,
+                '+' => {}
+                _ => term.push(c),
+            }
+        }
+
+        Ok(total)
+    }
+
+    fn term(&self, term: &str, depth: usize) -> Result<i64, String> {
+        if let Some(value) = parse_number(term) {
+            return Ok(value);
+        }
+
+        match self.symbols.get(term) {
+            Some(Symbol::Label(address)) => Ok(*address as i64),
+            Some(Symbol::Define(value)) => self.evaluate(value, depth + 1),
+            None if !self.final_pass && is_identifier(term) => Ok(0),
+            None if is_identifier(term) => Err(format!("Unknown symbol: {term}")),
+            None => Err(format!("Invalid value: {term}")),
+        }
+    }
+}
+
+fn register(operand: &str) -> Option<u8> {
+    let digit = operand
+        .strip_prefix(['V', 'v'])
+        .filter(|digit| digit.len() == 1)?;
+    u8::from_str_radix(digit, 16).ok()
+}
+
+// Decimal, hex (`0x1F`, `$1F`, `#1F`) or binary (`0b101`, `%101`).
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+
+    if let Some(hex) = ["0x", "$", "#"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+    {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = ["0b", "%"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+    {
+        i64::from_str_radix(binary, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
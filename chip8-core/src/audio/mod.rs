@@ -0,0 +1,298 @@
+pub mod ring;
+
+use alloc::{format, string::String};
+use core::str::FromStr;
+
+use crate::{
+    audio::ring::Producer,
+    emulator::consts::{AUDIO_PATTERN_SIZE, DEFAULT_PITCH, NUM_BITS_IN_BYTE},
+};
+
+const PATTERN_BITS: f32 = (AUDIO_PATTERN_SIZE * NUM_BITS_IN_BYTE) as f32;
+
+// Where the emulator sends its sound, once per timer tick.
+pub trait AudioSink {
+    fn resume(&mut self);
+    fn pause(&mut self);
+    // `pattern` is None until a ROM loads one with F002, in which case the plain tone is played.
+    fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, pitch: u8);
+    // Called at the end of every timer tick, once the sound for it is set, for
+    // sinks that make the samples of each 1/60 of a second themselves.
+    fn tick(&mut self) {}
+}
+
+// Stands in for a sound device when there is none, or when sound is muted.
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn resume(&mut self) {}
+    fn pause(&mut self) {}
+    fn set_pattern(&mut self, _pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, _pitch: u8) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    // A new random level on every period, so `frequency` sets how bright it sounds.
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            "noise" => Ok(Waveform::Noise),
+            _ => Err(format!("Unknown waveform: {s}")),
+        }
+    }
+}
+
+// How the plain tone sounds, for ROMs that don't load an XO-CHIP pattern.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Beep {
+    pub waveform: Waveform,
+    // In Hz.
+    pub frequency: f32,
+    // From 0 to 1.
+    pub volume: f32,
+    // Fraction of each period the square wave spends high.
+    pub duty_cycle: f32,
+    // Milliseconds the volume takes to rise when the sound starts and to fall
+    // when it stops, so that it doesn't click.
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl Default for Beep {
+    fn default() -> Self {
+        Beep {
+            waveform: Waveform::Square,
+            frequency: 150.0,
+            volume: 0.05,
+            duty_cycle: 0.5,
+            attack: 5.0,
+            release: 5.0,
+        }
+    }
+}
+
+impl Beep {
+    // Changes a single setting by name, e.g. `set("frequency", "440")`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name == "waveform" {
+            self.waveform = value.parse()?;
+            return Ok(());
+        }
+
+        let (setting, range) = match name {
+            "frequency" => (&mut self.frequency, 1.0..=20_000.0),
+            "volume" => (&mut self.volume, 0.0..=1.0),
+            "duty_cycle" => (&mut self.duty_cycle, 0.0..=1.0),
+            "attack" => (&mut self.attack, 0.0..=1000.0),
+            "release" => (&mut self.release, 0.0..=1000.0),
+            _ => return Err(format!("Unknown beep setting: {name}")),
+        };
+
+        *setting = value
+            .parse()
+            .ok()
+            .filter(|value| range.contains(value))
+            .ok_or(format!(
+                "Invalid value for {name}: {value} (expected {} to {})",
+                range.start(),
+                range.end()
+            ))?;
+
+        Ok(())
+    }
+
+    // Applies comma-separated settings, optionally starting with a waveform,
+    // e.g. `sine,frequency=440,volume=0.1`.
+    pub fn apply(&mut self, settings: &str) -> Result<(), String> {
+        for (i, part) in settings.split(',').map(str::trim).enumerate() {
+            match part.split_once('=') {
+                Some((name, value)) => self.set(name.trim(), value.trim())?,
+                None if i == 0 => self.waveform = part.parse()?,
+                None => return Err(format!("Expected name=value, found \"{part}\"")),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Generates the samples of a beep, ramping its volume up and down as the sound
+// is turned on and off.
+pub struct Tone {
+    pub beep: Beep,
+    sample_rate: f32,
+    // Position in the current period, from 0 to 1.
+    phase: f32,
+    // Linear-feedback shift register behind the noise.
+    noise: u16,
+    // Current volume of the envelope, from 0 to 1.
+    level: f32,
+}
+
+impl Tone {
+    pub fn new(beep: Beep, sample_rate: f32) -> Tone {
+        Tone {
+            beep,
+            sample_rate,
+            phase: 0.0,
+            noise: 1,
+            level: 0.0,
+        }
+    }
+
+    // The next sample of the waveform, from -1 to 1, before the volume is applied.
+    pub fn wave(&mut self) -> f32 {
+        let phase = self.phase;
+        self.phase += self.beep.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            let bit = (self.noise ^ (self.noise >> 1)) & 1;
+            self.noise = (self.noise >> 1) | (bit << 14);
+        }
+
+        match self.beep.waveform {
+            Waveform::Square if phase < self.beep.duty_cycle => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Sine => sine(phase),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Noise if self.noise & 1 != 0 => 1.0,
+            Waveform::Noise => -1.0,
+        }
+    }
+
+    // Moves the envelope on by one sample, towards full volume while `on` and
+    // towards silence otherwise, and returns the volume to play that sample at.
+    pub fn envelope(&mut self, on: bool) -> f32 {
+        let (target, milliseconds) = if on {
+            (1.0, self.beep.attack)
+        } else {
+            (0.0, self.beep.release)
+        };
+
+        let step = 1000.0 / (milliseconds * self.sample_rate);
+        self.level = if self.level < target {
+            (self.level + step).min(target)
+        } else {
+            (self.level - step).max(target)
+        };
+
+        self.level * self.beep.volume
+    }
+}
+
+// Turns the sound of the emulator into samples, 1/60 of a second's worth per
+// timer tick, so a sound lasts exactly as many ticks as the sound timer says
+// however the frames are paced. The samples go into a ring for the audio
+// callback to play.
+pub struct Synth {
+    tone: Tone,
+    sample_rate: u32,
+    on: bool,
+    pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    // Pattern playback rate, in bits per second.
+    pattern_rate: f32,
+    // Position in the pattern, counting bits from 0 to 128.
+    pattern_phase: f32,
+    // Samples left over from ticks that were owed a fraction of one, in 60ths.
+    sample_remainder: u32,
+    output: Producer,
+}
+
+impl Synth {
+    pub fn new(beep: Beep, sample_rate: u32, output: Producer) -> Synth {
+        Synth {
+            tone: Tone::new(beep, sample_rate as f32),
+            sample_rate,
+            on: false,
+            pattern: None,
+            pattern_rate: pitch_to_rate(DEFAULT_PITCH),
+            pattern_phase: 0.0,
+            sample_remainder: 0,
+            output,
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let volume = self.tone.envelope(self.on);
+
+        match self.pattern {
+            Some(pattern) => {
+                // Play the 128 bits of the pattern buffer as a 1-bit waveform.
+                let bit = self.pattern_phase as usize;
+                let byte = pattern[bit / NUM_BITS_IN_BYTE];
+                let is_set = byte & (0x80 >> (bit % NUM_BITS_IN_BYTE)) != 0;
+
+                self.pattern_phase = (self.pattern_phase
+                    + self.pattern_rate / self.sample_rate as f32)
+                    % PATTERN_BITS;
+                if is_set { volume } else { -volume }
+            }
+            None => self.tone.wave() * volume,
+        }
+    }
+}
+
+impl AudioSink for Synth {
+    fn resume(&mut self) {
+        self.on = true;
+    }
+    fn pause(&mut self) {
+        self.on = false;
+    }
+    fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, pitch: u8) {
+        self.pattern = pattern;
+        self.pattern_rate = pitch_to_rate(pitch);
+        self.pattern_phase = 0.0;
+    }
+    fn tick(&mut self) {
+        let owed = self.sample_rate + self.sample_remainder;
+        self.sample_remainder = owed % 60;
+
+        // Samples the player has no room for are dropped, so it never falls
+        // more than a ring behind the emulator.
+        for _ in 0..owed / 60 {
+            let sample = self.next_sample();
+            self.output.push(sample);
+        }
+    }
+}
+
+// XO-CHIP plays the pattern buffer at 4000 * 2^((pitch - 64) / 48) bits per
+// second. `core` has no `powf`, so the rate is moved a 48th of an octave at a time.
+fn pitch_to_rate(pitch: u8) -> f32 {
+    const STEP: f64 = 1.014_545_334_937_524; // 2^(1/48)
+
+    let steps = pitch as i32 - 64;
+    let mut rate = 4000.0;
+    for _ in 0..steps.unsigned_abs() {
+        if steps > 0 {
+            rate *= STEP;
+        } else {
+            rate /= STEP;
+        }
+    }
+    rate as f32
+}
+
+// sin(2πx) for x from 0 to 1, with Bhaskara I's approximation (off by less
+// than 0.2%) since `core` has no sine.
+fn sine(x: f32) -> f32 {
+    let (t, sign) = if x < 0.5 {
+        (2.0 * x, 1.0)
+    } else {
+        (2.0 * x - 1.0, -1.0)
+    };
+    let p = t * (1.0 - t);
+    sign * 16.0 * p / (5.0 - 4.0 * p)
+}
@@ -0,0 +1,72 @@
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+// A queue of samples between one thread that pushes them and one that pops
+// them, neither of which ever waits on the other: the emulator on one side,
+// the audio callback on the other.
+struct Ring {
+    // f32 samples, stored as their bits.
+    samples: Vec<AtomicU32>,
+    // Samples pushed and popped so far, wrapping around; the slot of a sample
+    // is its count modulo the capacity, which is a power of two.
+    pushed: AtomicUsize,
+    popped: AtomicUsize,
+}
+
+// Makes a ring holding at least `capacity` samples.
+pub fn ring(capacity: usize) -> (Producer, Consumer) {
+    let ring = Arc::new(Ring {
+        samples: (0..capacity.next_power_of_two())
+            .map(|_| AtomicU32::new(0))
+            .collect(),
+        pushed: AtomicUsize::new(0),
+        popped: AtomicUsize::new(0),
+    });
+
+    (Producer(ring.clone()), Consumer(ring))
+}
+
+pub struct Producer(Arc<Ring>);
+
+impl Producer {
+    // Adds a sample, unless the ring is full, in which case it is dropped and false is returned.
+    pub fn push(&mut self, sample: f32) -> bool {
+        let ring = &self.0;
+        let pushed = ring.pushed.load(Ordering::Relaxed);
+        if pushed.wrapping_sub(ring.popped.load(Ordering::Acquire)) == ring.samples.len() {
+            return false;
+        }
+
+        ring.samples[pushed & (ring.samples.len() - 1)].store(sample.to_bits(), Ordering::Relaxed);
+        ring.pushed.store(pushed.wrapping_add(1), Ordering::Release);
+        true
+    }
+}
+
+pub struct Consumer(Arc<Ring>);
+
+impl Consumer {
+    pub fn pop(&mut self) -> Option<f32> {
+        let ring = &self.0;
+        let popped = ring.popped.load(Ordering::Relaxed);
+        if popped == ring.pushed.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let sample = ring.samples[popped & (ring.samples.len() - 1)].load(Ordering::Relaxed);
+        ring.popped.store(popped.wrapping_add(1), Ordering::Release);
+        Some(f32::from_bits(sample))
+    }
+
+    // Samples waiting to be popped.
+    pub fn len(&self) -> usize {
+        let ring = &self.0;
+        ring.pushed
+            .load(Ordering::Acquire)
+            .wrapping_sub(ring.popped.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
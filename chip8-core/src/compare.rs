@@ -0,0 +1,249 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt;
+
+use crate::{
+    audio::NullAudio,
+    disasm::instruction::Instruction,
+    emulator::{
+        Emulator,
+        error::{EmulatorError, StepOutcome},
+    },
+    json::{self, Value},
+    scheduler::Scheduler,
+};
+
+// The state of the CPU before one instruction of a reference trace, recorded by
+// another emulator. Fields left out of the trace are not compared.
+//
+// Traces are text, one step per line with hex `NAME=VALUE` fields:
+//
+//   PC=0200 I=0000 V0=00 V1=00 ... VF=00 DT=00 ST=00
+//
+// or a JSON array with one object per step:
+//
+//   [{ "pc": 512, "i": 0, "v": [0, 0, ..., 0], "dt": 0, "st": 0 }, ...]
+//
+// where numbers may also be strings such as "0x200". Text lines starting with
+// `#` and unknown fields, such as an opcode, are skipped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Step {
+    pub pc: Option<u16>,
+    pub index_register: Option<u16>,
+    pub v_registers: [Option<u8>; 16],
+    pub delay_timer: Option<u8>,
+    pub sound_timer: Option<u8>,
+}
+
+pub fn parse_trace(text: &str) -> Result<Vec<Step>, String> {
+    if text.trim_start().starts_with('[') {
+        parse_json_trace(text)
+    } else {
+        parse_text_trace(text)
+    }
+}
+
+fn parse_text_trace(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut step = Step::default();
+        for field in line.split_whitespace() {
+            let Some((name, value)) = field
+                .split_once('=')
+                .filter(|(name, _)| register_number(name).is_some() || is_field(name))
+            else {
+                continue;
+            };
+            let hex = value.trim_start_matches("0x");
+            let value = u16::from_str_radix(hex, 16)
+                .map_err(|_| format!("Invalid value on line {}: {field}", i + 1))?;
+            step.set(name, value)
+                .map_err(|err| format!("{err} on line {}", i + 1))?;
+        }
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+fn parse_json_trace(text: &str) -> Result<Vec<Step>, String> {
+    let Value::Array(items) = json::parse(text)? else {
+        return Err(String::from("Expected a JSON array of steps"));
+    };
+
+    let mut steps = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let Value::Object(members) = item else {
+            return Err(format!("Step {i} is not a JSON object"));
+        };
+
+        let mut step = Step::default();
+        for (name, value) in members {
+            let values = match value {
+                Value::Array(values) if name.eq_ignore_ascii_case("v") => values
+                    .iter()
+                    .enumerate()
+                    .map(|(x, value)| (format!("V{x:X}"), value))
+                    .collect(),
+                _ => vec![(name.clone(), value)],
+            };
+
+            for (name, value) in values {
+                let value = value
+                    .as_u64()
+                    .and_then(|value| u16::try_from(value).ok())
+                    .ok_or(format!("Invalid value for {name} in step {i}"))?;
+                step.set(&name, value)
+                    .map_err(|err| format!("{err} in step {i}"))?;
+            }
+        }
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+impl Step {
+    fn set(&mut self, name: &str, value: u16) -> Result<(), String> {
+        let byte = || u8::try_from(value).map_err(|_| format!("{name} out of range: {value:X}"));
+
+        match name.to_ascii_uppercase().as_str() {
+            "PC" => self.pc = Some(value),
+            "I" => self.index_register = Some(value),
+            "DT" => self.delay_timer = Some(byte()?),
+            "ST" => self.sound_timer = Some(byte()?),
+            _ => {
+                if let Some(x) = register_number(name) {
+                    self.v_registers[x] = Some(byte()?);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // The fields that don't match the emulator, as `PC: expected 0x0202, got 0x0204`.
+    fn differences(&self, emulator: &Emulator) -> Vec<String> {
+        let (delay_timer, sound_timer) = emulator.timers();
+        let mut differences = Vec::new();
+        let mut check = |name: &str, expected: Option<u16>, actual: u16, width: usize| {
+            if let Some(expected) = expected
+                && expected != actual
+            {
+                differences.push(format!(
+                    "{name}: expected 0x{expected:0width$X}, got 0x{actual:0width$X}"
+                ));
+            }
+        };
+
+        check("PC", self.pc, emulator.pc(), 4);
+        check("I", self.index_register, emulator.index_register(), 4);
+        for (x, (expected, actual)) in self
+            .v_registers
+            .iter()
+            .zip(emulator.v_registers())
+            .enumerate()
+        {
+            check(
+                &format!("V{x:X}"),
+                expected.map(u16::from),
+                *actual as u16,
+                2,
+            );
+        }
+        check("DT", self.delay_timer.map(u16::from), delay_timer as u16, 2);
+        check("ST", self.sound_timer.map(u16::from), sound_timer as u16, 2);
+
+        differences
+    }
+}
+
+fn is_field(name: &str) -> bool {
+    ["PC", "I", "DT", "ST"]
+        .iter()
+        .any(|field| field.eq_ignore_ascii_case(name))
+}
+
+// 3 for `V3` or `v3`.
+fn register_number(name: &str) -> Option<usize> {
+    name.strip_prefix(['V', 'v'])
+        .filter(|x| x.len() == 1)
+        .and_then(|x| usize::from_str_radix(x, 16).ok())
+}
+
+// Where the emulator first stopped agreeing with the reference trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // Index of the step in the trace, from 0.
+    pub step: usize,
+    // The instruction run just before, which got the emulator there.
+    pub previous: Option<(u16, Instruction)>,
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Diverged at step {}", self.step)?;
+        if let Some((pc, instruction)) = self.previous {
+            write!(f, ", after 0x{pc:04X} ({instruction})")?;
+        }
+        for difference in &self.differences {
+            write!(f, "\n  {difference}")?;
+        }
+        Ok(())
+    }
+}
+
+// Runs the emulator one instruction per step of the trace, checking its state
+// before each one. Frames end where they do in the window, after each frame's
+// share of the instructions per second or as the quirks decide (at the first
+// sprite drawn with `display_wait`, once the cycles run out with `vip_timing`),
+// and the timers tick as each one ends.
+pub fn compare(
+    emulator: &mut Emulator,
+    steps: &[Step],
+    instructions_per_second: u32,
+) -> Result<Option<Divergence>, EmulatorError> {
+    let mut previous = None;
+    let mut scheduler = Scheduler::new(instructions_per_second);
+    emulator.start_frame(scheduler.frame_instructions());
+    end_frames(emulator, &mut scheduler);
+
+    for (i, step) in steps.iter().enumerate() {
+        let differences = step.differences(emulator);
+        if !differences.is_empty() {
+            return Ok(Some(Divergence {
+                step: i,
+                previous,
+                differences,
+            }));
+        }
+
+        let pc = emulator.pc();
+        previous = Some((pc, emulator.instruction_at(pc)));
+        if emulator.execution_cycle()? == StepOutcome::Exited && i + 1 < steps.len() {
+            return Ok(Some(Divergence {
+                step: i + 1,
+                previous,
+                differences: vec![String::from("the ROM exited before the end of the trace")],
+            }));
+        }
+        end_frames(emulator, &mut scheduler);
+    }
+
+    Ok(None)
+}
+
+// Ticks the timers and starts the next frame for as long as the current one is
+// over. Below 60 instructions per second, some frames run none.
+fn end_frames(emulator: &mut Emulator, scheduler: &mut Scheduler) {
+    while emulator.frame_over() {
+        emulator.tick_timers(&mut NullAudio);
+        emulator.start_frame(scheduler.frame_instructions());
+    }
+}
@@ -0,0 +1,145 @@
+use alloc::{format, string::String};
+use core::{fmt, str::FromStr};
+
+use crate::emulator::Emulator;
+
+// A comparison between registers and numbers, such as `V3 == 0x10` or `I > 0xE00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    left: Operand,
+    comparison: Comparison,
+    right: Operand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Pc,
+    DelayTimer,
+    SoundTimer,
+    Number(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+impl Condition {
+    pub fn holds(&self, emulator: &Emulator) -> bool {
+        let left = self.left.value(emulator);
+        let right = self.right.value(emulator);
+
+        match self.comparison {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+impl Operand {
+    fn value(&self, emulator: &Emulator) -> u16 {
+        match *self {
+            Operand::V(x) => emulator.v_registers()[x] as u16,
+            Operand::I => emulator.index_register(),
+            Operand::Pc => emulator.pc(),
+            Operand::DelayTimer => emulator.timers().0 as u16,
+            Operand::SoundTimer => emulator.timers().1 as u16,
+            Operand::Number(value) => value,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, symbol, comparison) = COMPARISONS
+            .iter()
+            .filter_map(|&(symbol, comparison)| Some((s.find(symbol)?, symbol, comparison)))
+            // The leftmost operator wins, and `<=` wins over the `<` it starts with.
+            .min_by_key(|&(start, symbol, _)| (start, usize::MAX - symbol.len()))
+            .ok_or(format!("Missing comparison in condition: {s}"))?;
+
+        Ok(Condition {
+            left: s[..start].parse()?,
+            comparison,
+            right: s[(start + symbol.len())..].parse()?,
+        })
+    }
+}
+
+// Registers are named as in `V3`, `I`, `PC`, `DT` and `ST`. Numbers are
+// decimal unless prefixed with `0x` or `$`.
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_ascii_uppercase();
+
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "PC" => Operand::Pc,
+            "DT" => Operand::DelayTimer,
+            "ST" => Operand::SoundTimer,
+            _ if upper.len() == 2 && upper.starts_with('V') => {
+                let x = usize::from_str_radix(&upper[1..], 16)
+                    .map_err(|_| format!("Unknown register: {s}"))?;
+                Operand::V(x)
+            }
+            _ => {
+                let number = match upper.strip_prefix("0X").or(upper.strip_prefix('$')) {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => upper.parse(),
+                };
+                Operand::Number(number.map_err(|_| format!("Invalid operand: {s}"))?)
+            }
+        };
+
+        Ok(operand)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = COMPARISONS
+            .iter()
+            .find(|(_, comparison)| *comparison == self.comparison)
+            .map_or("", |(symbol, _)| symbol);
+
+        write!(f, "{} {symbol} {}", self.left, self.right)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::V(x) => write!(f, "V{x:X}"),
+            Operand::I => write!(f, "I"),
+            Operand::Pc => write!(f, "PC"),
+            Operand::DelayTimer => write!(f, "DT"),
+            Operand::SoundTimer => write!(f, "ST"),
+            Operand::Number(value) => write!(f, "0x{value:X}"),
+        }
+    }
+}
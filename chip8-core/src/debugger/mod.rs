@@ -0,0 +1,604 @@
+pub mod condition;
+
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::{fmt, fmt::Write, str::FromStr};
+
+use crate::{
+    audio::AudioSink,
+    debugger::condition::Condition,
+    disasm::{Syntax, instruction::Instruction},
+    emulator::{
+        Emulator, MemoryWrite,
+        error::{EmulatorError, StepOutcome},
+    },
+    symbols::Symbols,
+};
+
+pub const HELP: &str = "\
+Debugger commands (addresses are hex, or labels when a symbol map is loaded):
+  c, continue          Resume execution
+  p, pause             Pause execution
+  s, step              Run one instruction
+  n, next              Run one instruction, running a whole subroutine on 2nnn
+  o, out               Run until the current subroutine returns
+  b, break <ADDR> [if <COND>]
+                       Set a breakpoint at ADDR, optionally only taken when COND holds
+  d, delete <ADDR>     Remove the breakpoint at ADDR
+  w, watch <START> [END] [fx33|fx55]
+                       Stop after any write (or only Fx33/Fx55 writes) to START..=END
+  when <COND>          Stop as soon as COND becomes true, wherever the PC is
+  dw <N>               Remove watchpoint or condition number N
+  bl, breakpoints      List breakpoints, watchpoints and conditions
+  r, regs              Show registers, timers and stack
+  m, mem <ADDR> [LEN]  Show LEN bytes of memory starting at ADDR [default: 16]
+  h, help              Show this help
+
+Conditions compare registers (V0-VF, I, PC, DT, ST) and numbers with
+==, !=, <, <=, > or >=, e.g. `V3 == 0x10` or `I > 0xE00`.
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Continue,
+    Pause,
+    Step,
+    StepOver,
+    StepOut,
+    Break {
+        address: u16,
+        condition: Option<Condition>,
+    },
+    Delete(u16),
+    Watch(Watch),
+    Unwatch(usize),
+    Breakpoints,
+    Registers,
+    Memory {
+        address: u16,
+        len: u16,
+    },
+    Help,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Command::parse(s, &Symbols::default())
+    }
+}
+
+impl Command {
+    // Like `from_str`, but addresses may also be given as labels of `symbols`.
+    pub fn parse(s: &str, symbols: &Symbols) -> Result<Command, String> {
+        let parse_address = |s: &str| match symbols.address_of(s) {
+            Some(address) => Ok(address),
+            None => parse_address(s),
+        };
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let mut argument = || words.next().ok_or(format!("Missing argument for {name}"));
+
+        let command = match name {
+            "c" | "continue" => Command::Continue,
+            "p" | "pause" => Command::Pause,
+            "s" | "step" => Command::Step,
+            "n" | "next" => Command::StepOver,
+            "o" | "out" => Command::StepOut,
+            "b" | "break" => {
+                let address = parse_address(argument()?)?;
+                let condition = match words.next() {
+                    Some("if") => Some(words.collect::<Vec<_>>().join(" ").parse()?),
+                    Some(word) => return Err(format!("Unexpected argument: {word}")),
+                    None => None,
+                };
+                Command::Break { address, condition }
+            }
+            "d" | "delete" => Command::Delete(parse_address(argument()?)?),
+            "w" | "watch" => {
+                let start = parse_address(argument()?)?;
+                let mut end = start;
+                let mut filter = WriteFilter::Any;
+
+                for (i, word) in words.enumerate() {
+                    match word.to_ascii_lowercase().as_str() {
+                        "fx33" => filter = WriteFilter::Fx33,
+                        "fx55" => filter = WriteFilter::Fx55,
+                        _ if i == 0 => end = parse_address(word)?,
+                        _ => return Err(format!("Unexpected argument: {word}")),
+                    }
+                }
+                if end < start {
+                    return Err(format!("Empty address range: {start:X}..={end:X}"));
+                }
+
+                Command::Watch(Watch::Write { start, end, filter })
+            }
+            "when" => Command::Watch(Watch::Condition(
+                words.collect::<Vec<_>>().join(" ").parse()?,
+            )),
+            "dw" => {
+                let number = argument()?;
+                Command::Unwatch(
+                    number
+                        .parse()
+                        .map_err(|_| format!("Invalid number: {number}"))?,
+                )
+            }
+            "bl" | "breakpoints" => Command::Breakpoints,
+            "r" | "regs" => Command::Registers,
+            "m" | "mem" => {
+                let address = parse_address(argument()?)?;
+                let len = match words.next() {
+                    Some(len) => len.parse().map_err(|_| format!("Invalid length: {len}"))?,
+                    None => 16,
+                };
+                Command::Memory { address, len }
+            }
+            "h" | "help" => Command::Help,
+            _ => return Err(format!("Unknown command: {name}")),
+        };
+
+        Ok(command)
+    }
+}
+
+// Accepts `2a0`, `0x2A0` and `$2a0`.
+fn parse_address(s: &str) -> Result<u16, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix('$'))
+        .unwrap_or(s);
+    u16::from_str_radix(hex, 16).map_err(|_| format!("Invalid address: {s}"))
+}
+
+// Stops the emulator wherever the PC is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    // Memory writes to `start..=end`.
+    Write {
+        start: u16,
+        end: u16,
+        filter: WriteFilter,
+    },
+    Condition(Condition),
+}
+
+// Which instructions a write watchpoint looks at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteFilter {
+    Any,
+    Fx33,
+    Fx55,
+}
+
+impl Watch {
+    fn is_triggered(&self, emulator: &Emulator) -> bool {
+        match self {
+            Watch::Write { start, end, filter } => emulator.last_write().is_some_and(|write| {
+                let matches_filter = match filter {
+                    WriteFilter::Any => true,
+                    WriteFilter::Fx33 => matches!(write.instruction, Instruction::Bcd { .. }),
+                    WriteFilter::Fx55 => matches!(write.instruction, Instruction::Save { .. }),
+                };
+                matches_filter
+                    && write.range.start <= *end as usize
+                    && write.range.end > *start as usize
+            }),
+            Watch::Condition(condition) => condition.holds(emulator),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Write { start, end, filter } => {
+                write!(f, "writes to 0x{start:04X}..=0x{end:04X}")?;
+                match filter {
+                    WriteFilter::Any => Ok(()),
+                    WriteFilter::Fx33 => write!(f, " by Fx33"),
+                    WriteFilter::Fx55 => write!(f, " by Fx55"),
+                }
+            }
+            Watch::Condition(condition) => write!(f, "when {condition}"),
+        }
+    }
+}
+
+// Why the debugger stopped the emulator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Paused,
+    Step,
+    Breakpoint(u16),
+    Write(MemoryWrite),
+    Condition(Condition),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Paused => write!(f, "Paused"),
+            Stop::Step => write!(f, "Stepped"),
+            Stop::Breakpoint(address) => write!(f, "Breakpoint at 0x{address:04X}"),
+            Stop::Write(write) => write!(
+                f,
+                "0x{:04X} ({}) wrote 0x{:04X}..=0x{:04X}",
+                write.pc,
+                write.instruction,
+                write.range.start,
+                write.range.end - 1
+            ),
+            Stop::Condition(condition) => write!(f, "{condition}"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    paused: bool,
+    // Breakpoints with a condition are only taken when it holds.
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    // Each with whether it was triggered after the last instruction.
+    watches: Vec<(Watch, bool)>,
+    // Stack depth at which a running step over or step out is done.
+    return_depth: Option<usize>,
+    stop: Option<Stop>,
+    symbols: Symbols,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger::default()
+    }
+
+    // Labels are shown in place of addresses from then on, and the
+    // `:breakpoint` directives of the source become breakpoints.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        for &address in symbols.breakpoints().keys() {
+            self.breakpoints.insert(address, None);
+        }
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    // Parses a console command, resolving labels.
+    pub fn parse_command(&self, line: &str) -> Result<Command, String> {
+        Command::parse(line, &self.symbols)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // The reason of the last stop, reported once.
+    pub fn take_stop(&mut self) -> Option<Stop> {
+        self.stop.take()
+    }
+
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.stop_with(Stop::Paused);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.return_depth = None;
+    }
+
+    fn stop_with(&mut self, stop: Stop) {
+        self.paused = true;
+        self.return_depth = None;
+        self.stop = Some(stop);
+    }
+
+    // Runs a single instruction and stays paused.
+    pub fn step(&mut self, emulator: &mut Emulator) -> Result<StepOutcome, EmulatorError> {
+        let outcome = emulator.execution_cycle()?;
+        // Conditions that became true on the way are not reported, but must
+        // not stop the emulator again straight after continuing either.
+        self.triggered_watch(emulator);
+        self.stop_with(Stop::Step);
+        Ok(outcome)
+    }
+
+    // Like `step`, but a 2nnn call runs until the subroutine returns.
+    pub fn step_over(&mut self, emulator: &mut Emulator) -> Result<StepOutcome, EmulatorError> {
+        if !matches!(
+            emulator.instruction_at(emulator.pc()),
+            Instruction::Call { .. }
+        ) {
+            return self.step(emulator);
+        }
+
+        self.resume();
+        self.return_depth = Some(emulator.stack().len());
+        Ok(StepOutcome::Executed)
+    }
+
+    // Resumes until the subroutine being run returns. Returns false outside of any subroutine.
+    pub fn step_out(&mut self, emulator: &Emulator) -> bool {
+        let depth = emulator.stack().len();
+        if depth == 0 {
+            return false;
+        }
+
+        self.resume();
+        self.return_depth = Some(depth - 1);
+        true
+    }
+
+    pub fn set_breakpoint(&mut self, address: u16, condition: Option<Condition>) {
+        self.breakpoints.insert(address, condition);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) {
+        self.watches.push((watch, false));
+    }
+
+    // Watches are numbered from 1, in the order they were added.
+    pub fn remove_watch(&mut self, number: usize) -> Option<Watch> {
+        (1..=self.watches.len())
+            .contains(&number)
+            .then(|| self.watches.remove(number - 1).0)
+    }
+
+    // Same as `Emulator::run_frame`, but checks for breakpoints before every
+    // instruction. Nothing runs, not even the timers, while paused.
+    pub fn run_frame<T: AudioSink + ?Sized>(
+        &mut self,
+        emulator: &mut Emulator,
+        instructions: u32,
+        audio_device: &mut T,
+    ) -> Result<StepOutcome, EmulatorError> {
+        let mut outcome = StepOutcome::Executed;
+        if self.paused {
+            return Ok(outcome);
+        }
+
+        emulator.start_frame(instructions);
+        while !emulator.frame_over() {
+            outcome = emulator.execution_cycle()?;
+
+            if let Some(stop) = self.check(emulator) {
+                self.stop_with(stop);
+                break;
+            }
+            if outcome == StepOutcome::Exited {
+                break;
+            }
+        }
+
+        if outcome != StepOutcome::Exited {
+            emulator.tick_timers(audio_device);
+        }
+
+        Ok(outcome)
+    }
+
+    // Whether the emulator must stop, after an instruction and before running the one at PC.
+    fn check(&mut self, emulator: &Emulator) -> Option<Stop> {
+        if self
+            .return_depth
+            .is_some_and(|depth| emulator.stack().len() <= depth)
+        {
+            return Some(Stop::Step);
+        }
+
+        if let Some(watch) = self.triggered_watch(emulator) {
+            return Some(match watch {
+                Watch::Write { .. } => Stop::Write(emulator.last_write()?.clone()),
+                Watch::Condition(condition) => Stop::Condition(condition),
+            });
+        }
+
+        let pc = emulator.pc();
+        match self.breakpoints.get(&pc) {
+            Some(None) => Some(Stop::Breakpoint(pc)),
+            Some(Some(condition)) if condition.holds(emulator) => Some(Stop::Breakpoint(pc)),
+            _ => None,
+        }
+    }
+
+    // The first watch that stops the emulator after the last instruction.
+    // Conditions only do so as they become true, or continuing would stop again
+    // after every instruction for as long as they hold.
+    fn triggered_watch(&mut self, emulator: &Emulator) -> Option<Watch> {
+        let mut first = None;
+
+        for (watch, was_triggered) in &mut self.watches {
+            let triggered = watch.is_triggered(emulator);
+            let stops = match watch {
+                Watch::Write { .. } => triggered,
+                Watch::Condition(_) => triggered && !*was_triggered,
+            };
+            *was_triggered = triggered;
+
+            if stops && first.is_none() {
+                first = Some(*watch);
+            }
+        }
+
+        first
+    }
+
+    // Runs a console command, returning the text to show for it.
+    pub fn execute(
+        &mut self,
+        command: Command,
+        emulator: &mut Emulator,
+    ) -> Result<String, EmulatorError> {
+        let output = match command {
+            Command::Continue => {
+                self.resume();
+                String::from("Running\n")
+            }
+            Command::Pause => {
+                self.pause();
+                String::new()
+            }
+            Command::Step => {
+                self.step(emulator)?;
+                String::new()
+            }
+            Command::StepOver => {
+                self.step_over(emulator)?;
+                String::new()
+            }
+            Command::StepOut => {
+                if self.step_out(emulator) {
+                    String::new()
+                } else {
+                    String::from("Not inside a subroutine\n")
+                }
+            }
+            Command::Break { address, condition } => {
+                self.set_breakpoint(address, condition);
+                let at = self.address_name(address);
+                match condition {
+                    Some(condition) => format!("Breakpoint set at {at} if {condition}\n"),
+                    None => format!("Breakpoint set at {at}\n"),
+                }
+            }
+            Command::Delete(address) => {
+                let at = self.address_name(address);
+                if self.remove_breakpoint(address) {
+                    format!("Breakpoint at {at} removed\n")
+                } else {
+                    format!("No breakpoint at {at}\n")
+                }
+            }
+            Command::Watch(watch) => {
+                self.add_watch(watch);
+                format!("Watch {} set: {watch}\n", self.watches.len())
+            }
+            Command::Unwatch(number) => match self.remove_watch(number) {
+                Some(watch) => format!("Watch {number} removed: {watch}\n"),
+                None => format!("No watch number {number}\n"),
+            },
+            Command::Breakpoints => self.list(),
+            Command::Registers => self.registers(emulator),
+            Command::Memory { address, len } => memory_dump(emulator, address, len),
+            Command::Help => String::from(HELP),
+        };
+
+        Ok(output)
+    }
+
+    fn list(&self) -> String {
+        if self.breakpoints.is_empty() && self.watches.is_empty() {
+            return String::from("No breakpoints\n");
+        }
+
+        let mut text = String::new();
+        for (address, condition) in &self.breakpoints {
+            let at = self.address_name(*address);
+            let _ = match condition {
+                Some(condition) => writeln!(text, "Breakpoint at {at} if {condition}"),
+                None => writeln!(text, "Breakpoint at {at}"),
+            };
+        }
+        for (i, (watch, _)) in self.watches.iter().enumerate() {
+            let _ = writeln!(text, "Watch {}: {watch}", i + 1);
+        }
+
+        text
+    }
+
+    // What a stop is reported with: its reason, the name of a `:breakpoint`
+    // directive stopped on, and the registers.
+    pub fn report(&self, stop: &Stop, emulator: &Emulator) -> String {
+        let mut text = match stop {
+            Stop::Breakpoint(address) => match self.symbols.breakpoints().get(address) {
+                Some(name) => format!("Breakpoint {name} at {}", self.address_name(*address)),
+                None => format!("Breakpoint at {}", self.address_name(*address)),
+            },
+            _ => format!("{stop}"),
+        };
+        text.push('\n');
+        text.push_str(&self.registers(emulator));
+
+        text
+    }
+
+    // Everything the CPU holds, e.g.
+    //
+    // PC 0x0234 <loop+4> (6A02: LD VA, 0x02)  I 0x0F00  DT 00  ST 00
+    // V0 00  V1 00  V2 00  V3 10  V4 00  V5 00  V6 00  V7 00
+    // V8 00  V9 00  VA 00  VB 00  VC 00  VD 00  VE 00  VF 01
+    // Stack: 0x0202 <main+2> 0x0310
+    // Source: game.8o:42
+    //
+    // Labels and the source line only show up once a symbol map is loaded.
+    pub fn registers(&self, emulator: &Emulator) -> String {
+        let pc = emulator.pc();
+        let (delay_timer, sound_timer) = emulator.timers();
+        let mut text = format!(
+            "PC {} ({:04X}: {})  I 0x{:04X}  DT {delay_timer:02X}  ST {sound_timer:02X}",
+            self.address_name(pc),
+            emulator.read_word(pc),
+            emulator
+                .instruction_at(pc)
+                .render(Syntax::Classic, self.symbols.labels()),
+            emulator.index_register()
+        );
+
+        for (i, value) in emulator.v_registers().iter().enumerate() {
+            let separator = if i % 8 == 0 { "\n" } else { "  " };
+            let _ = write!(text, "{separator}V{i:X} {value:02X}");
+        }
+
+        text.push_str("\nStack:");
+        if emulator.stack().is_empty() {
+            text.push_str(" empty");
+        }
+        for address in emulator.stack() {
+            let _ = write!(text, " {}", self.address_name(*address));
+        }
+
+        if let Some(line) = self.symbols.source_line(pc) {
+            let _ = match self.symbols.source() {
+                Some(source) => write!(text, "\nSource: {source}:{line}"),
+                None => write!(text, "\nSource: line {line}"),
+            };
+        }
+        text.push('\n');
+
+        text
+    }
+
+    // `0x0234`, or `0x0234 <loop+4>` when a label comes before it.
+    fn address_name(&self, address: u16) -> String {
+        match self.symbols.describe(address) {
+            Some(name) => format!("0x{address:04X} <{name}>"),
+            None => format!("0x{address:04X}"),
+        }
+    }
+}
+
+// 16 bytes per line, clamped to the end of memory.
+fn memory_dump(emulator: &Emulator, address: u16, len: u16) -> String {
+    let memory = emulator.memory();
+    let start = (address as usize).min(memory.len());
+    let end = (start + len as usize).min(memory.len());
+
+    let mut text = String::new();
+    for (i, line) in memory[start..end].chunks(16).enumerate() {
+        let _ = write!(text, "0x{:04X}:", start + i * 16);
+        for byte in line {
+            let _ = write!(text, " {byte:02X}");
+        }
+        text.push('\n');
+    }
+
+    text
+}
@@ -0,0 +1,404 @@
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+use core::fmt;
+
+use crate::disasm::Syntax;
+
+// Every instruction of CHIP-8, SUPER-CHIP and XO-CHIP, with its operands.
+// `x` and `y` are register numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 00Cn
+    ScrollDown { n: u8 },
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    LowRes,
+    // 00FF
+    HighRes,
+    // 0nnn
+    MachineCall { address: u16 },
+    // 1nnn
+    Jump { address: u16 },
+    // 2nnn
+    Call { address: u16 },
+    // 3xkk
+    SkipIfEqual { x: u8, byte: u8 },
+    // 4xkk
+    SkipIfNotEqual { x: u8, byte: u8 },
+    // 5xy0
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    // 5xy2
+    SaveRange { x: u8, y: u8 },
+    // 5xy3
+    LoadRange { x: u8, y: u8 },
+    // 6xkk
+    Set { x: u8, byte: u8 },
+    // 7xkk
+    Add { x: u8, byte: u8 },
+    // 8xy0
+    Move { x: u8, y: u8 },
+    // 8xy1
+    Or { x: u8, y: u8 },
+    // 8xy2
+    And { x: u8, y: u8 },
+    // 8xy3
+    Xor { x: u8, y: u8 },
+    // 8xy4
+    AddRegisters { x: u8, y: u8 },
+    // 8xy5
+    Sub { x: u8, y: u8 },
+    // 8xy6
+    ShiftRight { x: u8, y: u8 },
+    // 8xy7
+    SubReversed { x: u8, y: u8 },
+    // 8xyE
+    ShiftLeft { x: u8, y: u8 },
+    // 9xy0
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    // Annn
+    SetIndex { address: u16 },
+    // Bnnn, read as Bxnn with the jump quirk
+    JumpOffset { x: u8, address: u16 },
+    // Cxkk
+    Random { x: u8, byte: u8 },
+    // Dxyn
+    Draw { x: u8, y: u8, n: u8 },
+    // Ex9E
+    SkipIfKey { x: u8 },
+    // ExA1
+    SkipIfNotKey { x: u8 },
+    // F000 nnnn
+    SetIndexLong { address: u16 },
+    // Fn01
+    SelectPlanes { n: u8 },
+    // F002
+    LoadAudio,
+    // Fx07
+    GetDelay { x: u8 },
+    // Fx0A
+    WaitKey { x: u8 },
+    // Fx15
+    SetDelay { x: u8 },
+    // Fx18
+    SetSound { x: u8 },
+    // Fx1E
+    AddIndex { x: u8 },
+    // Fx29
+    Font { x: u8 },
+    // Fx30
+    BigFont { x: u8 },
+    // Fx3A
+    SetPitch { x: u8 },
+    // Fx33
+    Bcd { x: u8 },
+    // Fx55
+    Save { x: u8 },
+    // Fx65
+    Load { x: u8 },
+    // Fx75
+    SaveFlags { x: u8 },
+    // Fx85
+    LoadFlags { x: u8 },
+    Unknown { opcode: u16 },
+}
+
+impl Instruction {
+    // `next` is the word following `opcode` in memory, only used by F000 nnnn.
+    pub fn decode(opcode: u16, next: u16) -> Instruction {
+        let nibble1 = (opcode >> 12) as u8;
+        let x = ((opcode >> 8) & 0xF) as u8;
+        let y = ((opcode >> 4) & 0xF) as u8;
+        let n = (opcode & 0xF) as u8;
+
+        let address = opcode & 0x0FFF;
+        let byte = opcode as u8;
+
+        match (nibble1, x, y, n) {
+            (0, 0, 0xC, _) => Instruction::ScrollDown { n },
+            (0, 0, 0xE, 0) => Instruction::Clear,
+            (0, 0, 0xE, 0xE) => Instruction::Return,
+            (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::LowRes,
+            (0, 0, 0xF, 0xF) => Instruction::HighRes,
+            (0, _, _, _) => Instruction::MachineCall { address },
+            (1, _, _, _) => Instruction::Jump { address },
+            (2, _, _, _) => Instruction::Call { address },
+            (3, _, _, _) => Instruction::SkipIfEqual { x, byte },
+            (4, _, _, _) => Instruction::SkipIfNotEqual { x, byte },
+            (5, _, _, 0) => Instruction::SkipIfRegistersEqual { x, y },
+            (5, _, _, 2) => Instruction::SaveRange { x, y },
+            (5, _, _, 3) => Instruction::LoadRange { x, y },
+            (6, _, _, _) => Instruction::Set { x, byte },
+            (7, _, _, _) => Instruction::Add { x, byte },
+            (8, _, _, 0) => Instruction::Move { x, y },
+            (8, _, _, 1) => Instruction::Or { x, y },
+            (8, _, _, 2) => Instruction::And { x, y },
+            (8, _, _, 3) => Instruction::Xor { x, y },
+            (8, _, _, 4) => Instruction::AddRegisters { x, y },
+            (8, _, _, 5) => Instruction::Sub { x, y },
+            (8, _, _, 6) => Instruction::ShiftRight { x, y },
+            (8, _, _, 7) => Instruction::SubReversed { x, y },
+            (8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+            (9, _, _, 0) => Instruction::SkipIfRegistersNotEqual { x, y },
+            (0xA, _, _, _) => Instruction::SetIndex { address },
+            (0xB, _, _, _) => Instruction::JumpOffset { x, address },
+            (0xC, _, _, _) => Instruction::Random { x, byte },
+            (0xD, _, _, _) => Instruction::Draw { x, y, n },
+            (0xE, _, 0x9, 0xE) => Instruction::SkipIfKey { x },
+            (0xE, _, 0xA, 0x1) => Instruction::SkipIfNotKey { x },
+            (0xF, 0, 0x0, 0x0) => Instruction::SetIndexLong { address: next },
+            (0xF, _, 0x0, 0x1) => Instruction::SelectPlanes { n: x },
+            (0xF, 0, 0x0, 0x2) => Instruction::LoadAudio,
+            (0xF, _, 0x0, 0x7) => Instruction::GetDelay { x },
+            (0xF, _, 0x0, 0xA) => Instruction::WaitKey { x },
+            (0xF, _, 0x1, 0x5) => Instruction::SetDelay { x },
+            (0xF, _, 0x1, 0x8) => Instruction::SetSound { x },
+            (0xF, _, 0x1, 0xE) => Instruction::AddIndex { x },
+            (0xF, _, 0x2, 0x9) => Instruction::Font { x },
+            (0xF, _, 0x3, 0x0) => Instruction::BigFont { x },
+            (0xF, _, 0x3, 0xA) => Instruction::SetPitch { x },
+            (0xF, _, 0x3, 0x3) => Instruction::Bcd { x },
+            (0xF, _, 0x5, 0x5) => Instruction::Save { x },
+            (0xF, _, 0x6, 0x5) => Instruction::Load { x },
+            (0xF, _, 0x7, 0x5) => Instruction::SaveFlags { x },
+            (0xF, _, 0x8, 0x5) => Instruction::LoadFlags { x },
+            _ => Instruction::Unknown { opcode },
+        }
+    }
+
+    // Appends the `size()` bytes of the instruction, the reverse of `decode`.
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        let xy = |opcode: u16, x: u8, y: u8| opcode | (x as u16) << 8 | (y as u16) << 4;
+        let xkk = |opcode: u16, x: u8, byte: u8| opcode | (x as u16) << 8 | byte as u16;
+        let fx = |opcode: u16, x: u8| opcode | (x as u16) << 8;
+
+        let opcode = match *self {
+            Instruction::ScrollDown { n } => 0x00C0 | n as u16,
+            Instruction::Clear => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::MachineCall { address } => address,
+            Instruction::Jump { address } => 0x1000 | address,
+            Instruction::Call { address } => 0x2000 | address,
+            Instruction::SkipIfEqual { x, byte } => xkk(0x3000, x, byte),
+            Instruction::SkipIfNotEqual { x, byte } => xkk(0x4000, x, byte),
+            Instruction::SkipIfRegistersEqual { x, y } => xy(0x5000, x, y),
+            Instruction::SaveRange { x, y } => xy(0x5002, x, y),
+            Instruction::LoadRange { x, y } => xy(0x5003, x, y),
+            Instruction::Set { x, byte } => xkk(0x6000, x, byte),
+            Instruction::Add { x, byte } => xkk(0x7000, x, byte),
+            Instruction::Move { x, y } => xy(0x8000, x, y),
+            Instruction::Or { x, y } => xy(0x8001, x, y),
+            Instruction::And { x, y } => xy(0x8002, x, y),
+            Instruction::Xor { x, y } => xy(0x8003, x, y),
+            Instruction::AddRegisters { x, y } => xy(0x8004, x, y),
+            Instruction::Sub { x, y } => xy(0x8005, x, y),
+            Instruction::ShiftRight { x, y } => xy(0x8006, x, y),
+            Instruction::SubReversed { x, y } => xy(0x8007, x, y),
+            Instruction::ShiftLeft { x, y } => xy(0x800E, x, y),
+            Instruction::SkipIfRegistersNotEqual { x, y } => xy(0x9000, x, y),
+            Instruction::SetIndex { address } => 0xA000 | address,
+            // x is the top nibble of the address, as far as the interpreter is concerned.
+            Instruction::JumpOffset { address, .. } => 0xB000 | address,
+            Instruction::Random { x, byte } => xkk(0xC000, x, byte),
+            Instruction::Draw { x, y, n } => xy(0xD000, x, y) | n as u16,
+            Instruction::SkipIfKey { x } => fx(0xE09E, x),
+            Instruction::SkipIfNotKey { x } => fx(0xE0A1, x),
+            Instruction::SetIndexLong { .. } => 0xF000,
+            Instruction::SelectPlanes { n } => fx(0xF001, n),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::GetDelay { x } => fx(0xF007, x),
+            Instruction::WaitKey { x } => fx(0xF00A, x),
+            Instruction::SetDelay { x } => fx(0xF015, x),
+            Instruction::SetSound { x } => fx(0xF018, x),
+            Instruction::AddIndex { x } => fx(0xF01E, x),
+            Instruction::Font { x } => fx(0xF029, x),
+            Instruction::BigFont { x } => fx(0xF030, x),
+            Instruction::SetPitch { x } => fx(0xF03A, x),
+            Instruction::Bcd { x } => fx(0xF033, x),
+            Instruction::Save { x } => fx(0xF055, x),
+            Instruction::Load { x } => fx(0xF065, x),
+            Instruction::SaveFlags { x } => fx(0xF075, x),
+            Instruction::LoadFlags { x } => fx(0xF085, x),
+            Instruction::Unknown { opcode } => opcode,
+        };
+        bytes.extend(opcode.to_be_bytes());
+
+        if let Instruction::SetIndexLong { address } = *self {
+            bytes.extend(address.to_be_bytes());
+        }
+    }
+
+    // Size in bytes: F000 nnnn takes two words, everything else one.
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::SetIndexLong { .. } => 4,
+            _ => 2,
+        }
+    }
+
+    // Whether this is one of the instructions that skip the next one.
+    pub fn is_skip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::SkipIfEqual { .. }
+                | Instruction::SkipIfNotEqual { .. }
+                | Instruction::SkipIfRegistersEqual { .. }
+                | Instruction::SkipIfRegistersNotEqual { .. }
+                | Instruction::SkipIfKey { .. }
+                | Instruction::SkipIfNotKey { .. }
+        )
+    }
+
+    // Addresses are shown as their label when there's one.
+    pub fn render(&self, syntax: Syntax, labels: &BTreeMap<u16, String>) -> String {
+        let target = |address: u16| match labels.get(&address) {
+            Some(label) => label.clone(),
+            None => format!("0x{address:03X}"),
+        };
+
+        match syntax {
+            Syntax::Octo => self.octo(target),
+            Syntax::Classic => self.classic(target),
+        }
+    }
+
+    fn octo(&self, target: impl Fn(u16) -> String) -> String {
+        match *self {
+            Instruction::ScrollDown { n } => format!("scroll-down {n}"),
+            Instruction::Clear => String::from("clear"),
+            Instruction::Return => String::from("return"),
+            Instruction::ScrollRight => String::from("scroll-right"),
+            Instruction::ScrollLeft => String::from("scroll-left"),
+            Instruction::Exit => String::from("exit"),
+            Instruction::LowRes => String::from("lores"),
+            Instruction::HighRes => String::from("hires"),
+            // Octo has no syntax for machine code calls, so they are written as data.
+            Instruction::MachineCall { address } => {
+                format!("0x{:02X} 0x{:02X}", address >> 8, address & 0xFF)
+            }
+            Instruction::Jump { address } => format!("jump {}", target(address)),
+            Instruction::Call { address } => format!(":call {}", target(address)),
+            // Octo conditions say when the next instruction runs, the opposite of when it is skipped.
+            Instruction::SkipIfEqual { x, byte } => format!("if v{x:x} != 0x{byte:02X} then"),
+            Instruction::SkipIfNotEqual { x, byte } => format!("if v{x:x} == 0x{byte:02X} then"),
+            Instruction::SkipIfRegistersEqual { x, y } => format!("if v{x:x} != v{y:x} then"),
+            Instruction::SaveRange { x, y } => format!("save v{x:x} - v{y:x}"),
+            Instruction::LoadRange { x, y } => format!("load v{x:x} - v{y:x}"),
+            Instruction::Set { x, byte } => format!("v{x:x} := 0x{byte:02X}"),
+            Instruction::Add { x, byte } => format!("v{x:x} += 0x{byte:02X}"),
+            Instruction::Move { x, y } => format!("v{x:x} := v{y:x}"),
+            Instruction::Or { x, y } => format!("v{x:x} |= v{y:x}"),
+            Instruction::And { x, y } => format!("v{x:x} &= v{y:x}"),
+            Instruction::Xor { x, y } => format!("v{x:x} ^= v{y:x}"),
+            Instruction::AddRegisters { x, y } => format!("v{x:x} += v{y:x}"),
+            Instruction::Sub { x, y } => format!("v{x:x} -= v{y:x}"),
+            Instruction::ShiftRight { x, y } => format!("v{x:x} >>= v{y:x}"),
+            Instruction::SubReversed { x, y } => format!("v{x:x} =- v{y:x}"),
+            Instruction::ShiftLeft { x, y } => format!("v{x:x} <<= v{y:x}"),
+            Instruction::SkipIfRegistersNotEqual { x, y } => format!("if v{x:x} == v{y:x} then"),
+            Instruction::SetIndex { address } => format!("i := {}", target(address)),
+            Instruction::JumpOffset { address, .. } => format!("jump0 {}", target(address)),
+            Instruction::Random { x, byte } => format!("v{x:x} := random 0x{byte:02X}"),
+            Instruction::Draw { x, y, n } => format!("sprite v{x:x} v{y:x} {n}"),
+            Instruction::SkipIfKey { x } => format!("if v{x:x} -key then"),
+            Instruction::SkipIfNotKey { x } => format!("if v{x:x} key then"),
+            Instruction::SetIndexLong { address } => format!("i := long {}", target(address)),
+            Instruction::SelectPlanes { n } => format!("plane {n}"),
+            Instruction::LoadAudio => String::from("audio"),
+            Instruction::GetDelay { x } => format!("v{x:x} := delay"),
+            Instruction::WaitKey { x } => format!("v{x:x} := key"),
+            Instruction::SetDelay { x } => format!("delay := v{x:x}"),
+            Instruction::SetSound { x } => format!("buzzer := v{x:x}"),
+            Instruction::AddIndex { x } => format!("i += v{x:x}"),
+            Instruction::Font { x } => format!("i := hex v{x:x}"),
+            Instruction::BigFont { x } => format!("i := bighex v{x:x}"),
+            Instruction::SetPitch { x } => format!("pitch := v{x:x}"),
+            Instruction::Bcd { x } => format!("bcd v{x:x}"),
+            Instruction::Save { x } => format!("save v{x:x}"),
+            Instruction::Load { x } => format!("load v{x:x}"),
+            Instruction::SaveFlags { x } => format!("saveflags v{x:x}"),
+            Instruction::LoadFlags { x } => format!("loadflags v{x:x}"),
+            Instruction::Unknown { opcode } => {
+                format!("0x{:02X} 0x{:02X}", opcode >> 8, opcode & 0xFF)
+            }
+        }
+    }
+
+    fn classic(&self, target: impl Fn(u16) -> String) -> String {
+        match *self {
+            Instruction::ScrollDown { n } => format!("SCD {n}"),
+            Instruction::Clear => String::from("CLS"),
+            Instruction::Return => String::from("RET"),
+            Instruction::ScrollRight => String::from("SCR"),
+            Instruction::ScrollLeft => String::from("SCL"),
+            Instruction::Exit => String::from("EXIT"),
+            Instruction::LowRes => String::from("LOW"),
+            Instruction::HighRes => String::from("HIGH"),
+            Instruction::MachineCall { address } => format!("SYS {}", target(address)),
+            Instruction::Jump { address } => format!("JP {}", target(address)),
+            Instruction::Call { address } => format!("CALL {}", target(address)),
+            Instruction::SkipIfEqual { x, byte } => format!("SE V{x:X}, 0x{byte:02X}"),
+            Instruction::SkipIfNotEqual { x, byte } => format!("SNE V{x:X}, 0x{byte:02X}"),
+            Instruction::SkipIfRegistersEqual { x, y } => format!("SE V{x:X}, V{y:X}"),
+            Instruction::SaveRange { x, y } => format!("SAVE V{x:X}, V{y:X}"),
+            Instruction::LoadRange { x, y } => format!("LOAD V{x:X}, V{y:X}"),
+            Instruction::Set { x, byte } => format!("LD V{x:X}, 0x{byte:02X}"),
+            Instruction::Add { x, byte } => format!("ADD V{x:X}, 0x{byte:02X}"),
+            Instruction::Move { x, y } => format!("LD V{x:X}, V{y:X}"),
+            Instruction::Or { x, y } => format!("OR V{x:X}, V{y:X}"),
+            Instruction::And { x, y } => format!("AND V{x:X}, V{y:X}"),
+            Instruction::Xor { x, y } => format!("XOR V{x:X}, V{y:X}"),
+            Instruction::AddRegisters { x, y } => format!("ADD V{x:X}, V{y:X}"),
+            Instruction::Sub { x, y } => format!("SUB V{x:X}, V{y:X}"),
+            Instruction::ShiftRight { x, y } => format!("SHR V{x:X}, V{y:X}"),
+            Instruction::SubReversed { x, y } => format!("SUBN V{x:X}, V{y:X}"),
+            Instruction::ShiftLeft { x, y } => format!("SHL V{x:X}, V{y:X}"),
+            Instruction::SkipIfRegistersNotEqual { x, y } => format!("SNE V{x:X}, V{y:X}"),
+            Instruction::SetIndex { address } => format!("LD I, {}", target(address)),
+            Instruction::JumpOffset { address, .. } => format!("JP V0, {}", target(address)),
+            Instruction::Random { x, byte } => format!("RND V{x:X}, 0x{byte:02X}"),
+            Instruction::Draw { x, y, n } => format!("DRW V{x:X}, V{y:X}, {n}"),
+            Instruction::SkipIfKey { x } => format!("SKP V{x:X}"),
+            Instruction::SkipIfNotKey { x } => format!("SKNP V{x:X}"),
+            Instruction::SetIndexLong { address } => format!("LD I, LONG {}", target(address)),
+            Instruction::SelectPlanes { n } => format!("PLANE {n}"),
+            Instruction::LoadAudio => String::from("AUDIO"),
+            Instruction::GetDelay { x } => format!("LD V{x:X}, DT"),
+            Instruction::WaitKey { x } => format!("LD V{x:X}, K"),
+            Instruction::SetDelay { x } => format!("LD DT, V{x:X}"),
+            Instruction::SetSound { x } => format!("LD ST, V{x:X}"),
+            Instruction::AddIndex { x } => format!("ADD I, V{x:X}"),
+            Instruction::Font { x } => format!("LD F, V{x:X}"),
+            Instruction::BigFont { x } => format!("LD HF, V{x:X}"),
+            Instruction::SetPitch { x } => format!("PITCH V{x:X}"),
+            Instruction::Bcd { x } => format!("LD B, V{x:X}"),
+            Instruction::Save { x } => format!("LD [I], V{x:X}"),
+            Instruction::Load { x } => format!("LD V{x:X}, [I]"),
+            Instruction::SaveFlags { x } => format!("LD R, V{x:X}"),
+            Instruction::LoadFlags { x } => format!("LD V{x:X}, R"),
+            Instruction::Unknown { opcode } => format!("DW 0x{opcode:04X}"),
+        }
+    }
+}
+
+// Classic mnemonics, without labels.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(Syntax::Classic, &BTreeMap::new()))
+    }
+}
@@ -0,0 +1,146 @@
+pub mod instruction;
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec,
+    vec::Vec,
+};
+use core::{fmt::Write, str::FromStr};
+
+use crate::{disasm::instruction::Instruction, emulator::consts::PROGRAM_START_ADDRESS};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    // The mnemonics of the Octo assembler, e.g. `v0 := 0x10`.
+    Octo,
+    // The mnemonics of Cowgod's technical reference, e.g. `LD V0, 0x10`.
+    Classic,
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "octo" => Ok(Syntax::Octo),
+            "classic" => Ok(Syntax::Classic),
+            _ => Err(format!("Unknown syntax: {s}")),
+        }
+    }
+}
+
+// Disassembles a ROM loaded at 0x200. Code is told apart from data by following
+// every path the program can take from 0x200, and whatever is never reached is
+// written as data bytes. Jump and call targets get labels such as `L2A0`.
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> String {
+    let (code, labels) = trace_code(rom);
+    let end = PROGRAM_START_ADDRESS + rom.len();
+
+    let mut text = String::new();
+    let mut data = Vec::new();
+    let mut address = PROGRAM_START_ADDRESS;
+
+    while address < end {
+        let instruction = code.get(&(address as u16)).filter(|instruction| {
+            // Only write whole instructions that nothing else jumps into.
+            (address + 1..address + instruction.size() as usize).all(|inner| {
+                !code.contains_key(&(inner as u16)) && !labels.contains_key(&(inner as u16))
+            })
+        });
+        let label = labels.get(&(address as u16));
+
+        if instruction.is_some() || label.is_some() {
+            write_data(&mut text, &mut data, syntax);
+        }
+        if let Some(label) = label {
+            let _ = match syntax {
+                Syntax::Octo => writeln!(text, ": {label}"),
+                Syntax::Classic => writeln!(text, "{label}:"),
+            };
+        }
+
+        match instruction {
+            Some(instruction) => {
+                let _ = writeln!(text, "\t{}", instruction.render(syntax, &labels));
+                address += instruction.size() as usize;
+            }
+            None => {
+                data.push(rom[address - PROGRAM_START_ADDRESS]);
+                address += 1;
+            }
+        }
+    }
+    write_data(&mut text, &mut data, syntax);
+
+    text
+}
+
+// Instructions reachable from 0x200 by address, and the labels of jump and call targets.
+fn trace_code(rom: &[u8]) -> (BTreeMap<u16, Instruction>, BTreeMap<u16, String>) {
+    let start = PROGRAM_START_ADDRESS;
+    let end = start + rom.len();
+    let word = |address: usize| -> u16 {
+        let byte = |address: usize| *rom.get(address.wrapping_sub(start)).unwrap_or(&0) as u16;
+        (byte(address) << 8) | byte(address + 1)
+    };
+
+    let mut code = BTreeMap::new();
+    let mut targets = BTreeSet::new();
+    let mut pending = vec![start as u16];
+
+    while let Some(address) = pending.pop() {
+        let position = address as usize;
+        if position < start || code.contains_key(&address) {
+            continue;
+        }
+
+        let instruction = Instruction::decode(word(position), word(position + 2));
+        if matches!(instruction, Instruction::Unknown { .. })
+            || position + instruction.size() as usize > end
+        {
+            continue;
+        }
+        code.insert(address, instruction);
+        let next = address.wrapping_add(instruction.size());
+
+        match instruction {
+            Instruction::Jump { address } => {
+                targets.insert(address);
+                pending.push(address);
+            }
+            Instruction::Call { address } => {
+                targets.insert(address);
+                pending.extend([address, next]);
+            }
+            // Bnnn depends on a register, so where it goes can't be known here.
+            Instruction::Return | Instruction::Exit | Instruction::JumpOffset { .. } => {}
+            _ if instruction.is_skip() => {
+                let skipped = Instruction::decode(word(next as usize), word(next as usize + 2));
+                pending.extend([next, next.wrapping_add(skipped.size())]);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    let labels = targets
+        .into_iter()
+        .filter(|&address| (start..end).contains(&(address as usize)))
+        .map(|address| (address, format!("L{address:03X}")))
+        .collect();
+
+    (code, labels)
+}
+
+// Writes out and clears the data bytes gathered so far, 8 per line.
+fn write_data(text: &mut String, data: &mut Vec<u8>, syntax: Syntax) {
+    for line in data.chunks(8) {
+        let bytes: Vec<String> = line.iter().map(|byte| format!("0x{byte:02X}")).collect();
+        let _ = match syntax {
+            Syntax::Octo => writeln!(text, "\t{}", bytes.join(" ")),
+            Syntax::Classic => writeln!(text, "\tDB {}", bytes.join(", ")),
+        };
+    }
+    data.clear();
+}
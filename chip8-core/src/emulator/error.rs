@@ -0,0 +1,44 @@
+use core::{error::Error, fmt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmulatorError {
+    // 00EE with nothing on the stack.
+    StackUnderflow,
+    // 2nnn with the stack already full.
+    StackOverflow,
+    InvalidOpcode { pc: u16, opcode: u16 },
+    // An instruction tried to read or write past the end of memory.
+    MemoryOutOfBounds { addr: usize },
+    // The ROM doesn't fit in memory after the interpreter area.
+    RomTooLarge { size: usize },
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::StackUnderflow => write!(f, "Stack underflow"),
+            EmulatorError::StackOverflow => write!(f, "Stack overflow"),
+            EmulatorError::InvalidOpcode { pc, opcode } => {
+                write!(f, "Invalid opcode {opcode:04X} at {pc:03X}")
+            }
+            EmulatorError::MemoryOutOfBounds { addr } => {
+                write!(f, "Memory access out of bounds at {addr:X}")
+            }
+            EmulatorError::RomTooLarge { size } => {
+                write!(f, "ROM too large for memory ({size} bytes)")
+            }
+        }
+    }
+}
+
+impl Error for EmulatorError {}
+
+// What happened during a successful execution cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    // Fx0A is still waiting for a key to be pressed and released.
+    WaitingForKey,
+    // 00FD was executed, or had been before.
+    Exited,
+}
@@ -0,0 +1,971 @@
+pub mod consts;
+pub mod error;
+pub mod hash;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod state;
+pub mod timing;
+pub mod trace;
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{cmp::min, ops::Range};
+
+use crate::{
+    audio::AudioSink,
+    disasm::instruction::Instruction,
+    emulator::{
+        consts::{
+            AUDIO_PATTERN_SIZE, BIG_FONTSET, BIG_FONTSET_SIZE, BIG_FONTSET_START_ADDRESS,
+            DEFAULT_PITCH, FONTSET, FONTSET_START_ADDRESS, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH,
+            MEMORY_SIZE, NUM_BITS_IN_BYTE, NUM_PLANES, PROGRAM_START_ADDRESS, SCREEN_HEIGHT,
+            SCREEN_WIDTH, STACK_SIZE, XO_CHIP_MEMORY_SIZE,
+        },
+        error::{EmulatorError, StepOutcome},
+        hash::sha256,
+        quirks::{IndexIncrement, Quirks},
+        rng::Rng,
+        timing::{CYCLES_PER_FRAME, VBLANK_CYCLES},
+        trace::{Snapshot, Tracer},
+    },
+};
+
+pub struct Emulator {
+    memory: Vec<u8>,
+    v_registers: [u8; 16],
+    index_register: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    btn_pressings: [bool; 16],
+    btn_waiting_for_release: Option<u8>,
+    rpl_flags: [u8; 16],
+    // The display always holds a hi-res frame; in low-res mode only its
+    // top-left SCREEN_WIDTH x SCREEN_HEIGHT corner is used.
+    // Each pixel is a bitmask of the XO-CHIP planes it is lit on.
+    pub display: [[u8; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+    hires: bool,
+    selected_planes: u8,
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    audio_changed: bool,
+    exited: bool,
+    // Set when a sprite is drawn, for frontends to clear once they have shown the frame.
+    pub draw_flag: bool,
+    quirks: Quirks,
+    rom_hash: [u8; 32],
+    rng: Rng,
+    // Instructions, or machine cycles with the `vip_timing` quirk, left to run
+    // in the current frame.
+    cycle_budget: i64,
+    last_write: Option<MemoryWrite>,
+    // Instructions decoded so far, by address. Writes to memory clear the entries they overlap.
+    decoded: Vec<Option<Instruction>>,
+    tracer: Option<Tracer>,
+}
+
+// Memory written by an instruction, as reported by `Emulator::last_write`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub pc: u16,
+    pub instruction: Instruction,
+    pub range: Range<usize>,
+}
+
+impl Emulator {
+    pub fn new(quirks: Quirks) -> Emulator {
+        let mut emu = Emulator {
+            memory: vec![
+                0;
+                if quirks.extended_memory {
+                    XO_CHIP_MEMORY_SIZE
+                } else {
+                    MEMORY_SIZE
+                }
+            ],
+            v_registers: [0; 16],
+            index_register: 0,
+            pc: 0x200,
+            stack: vec![],
+            delay_timer: 0,
+            sound_timer: 0,
+            btn_pressings: [false; 16],
+            rpl_flags: [0; 16],
+            display: [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+            hires: false,
+            selected_planes: 1,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            audio_changed: false,
+            exited: false,
+            draw_flag: false,
+            btn_waiting_for_release: None,
+            quirks,
+            rom_hash: [0; 32],
+            rng: Rng::from_entropy(),
+            cycle_budget: 0,
+            last_write: None,
+            decoded: vec![],
+            tracer: None,
+        };
+        emu.decoded = vec![None; emu.memory.len()];
+
+        emu.memory
+            [consts::FONTSET_START_ADDRESS..(consts::FONTSET_START_ADDRESS + consts::FONTSET_SIZE)]
+            .copy_from_slice(&FONTSET);
+        emu.memory[BIG_FONTSET_START_ADDRESS..(BIG_FONTSET_START_ADDRESS + BIG_FONTSET_SIZE)]
+            .copy_from_slice(&BIG_FONTSET);
+
+        emu
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let binary = std::fs::read(path)?;
+        self.load_rom_bytes(&binary)?;
+
+        Ok(())
+    }
+
+    pub fn load_rom_bytes(&mut self, binary: &[u8]) -> Result<(), EmulatorError> {
+        if binary.len() > self.memory.len() - PROGRAM_START_ADDRESS {
+            return Err(EmulatorError::RomTooLarge { size: binary.len() });
+        }
+
+        self.memory[PROGRAM_START_ADDRESS..(PROGRAM_START_ADDRESS + binary.len())]
+            .copy_from_slice(binary);
+        self.decoded.fill(None);
+        self.rom_hash = sha256(binary);
+
+        Ok(())
+    }
+
+    pub fn set_btn_press(&mut self, btn: u8, value: bool) {
+        if btn < 16 {
+            self.btn_pressings[btn as usize] = value;
+        }
+    }
+
+    // Which of the 16 buttons are held down.
+    pub fn buttons(&self) -> &[bool; 16] {
+        &self.btn_pressings
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    // SHA-256 of the ROM loaded last.
+    pub fn rom_hash(&self) -> &[u8; 32] {
+        &self.rom_hash
+    }
+
+    // Traces every instruction run from then on, or stops tracing with None.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    // Makes Cxkk produce the same numbers on every run.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    // The seed the random number generator started from, given or picked at startup.
+    pub fn rng_seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    // Width and height of the display in the current resolution mode.
+    pub fn screen_size(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn v_registers(&self) -> &[u8; 16] {
+        &self.v_registers
+    }
+
+    // Return addresses of the subroutines being run, innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    // Delay and sound timers, in that order.
+    pub fn timers(&self) -> (u8, u8) {
+        (self.delay_timer, self.sound_timer)
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    // The memory written by the last instruction run, if any.
+    pub fn last_write(&self) -> Option<&MemoryWrite> {
+        self.last_write.as_ref()
+    }
+
+    pub fn execution_cycle(&mut self) -> Result<StepOutcome, EmulatorError> {
+        if self.exited {
+            return Ok(StepOutcome::Exited);
+        }
+
+        let pc = self.pc;
+        let instruction = self.decode(pc);
+        self.pc = self.pc.wrapping_add(2);
+        self.last_write = None;
+
+        // Read before running, as the instruction may overwrite itself.
+        let before = self
+            .tracer
+            .as_ref()
+            .is_some_and(|tracer| tracer.traces(pc))
+            .then(|| {
+                let opcode =
+                    (self.read_word(pc) as u32) << 16 | self.read_word(pc.wrapping_add(2)) as u32;
+                (opcode, self.snapshot())
+            });
+
+        self.cycle_budget -= if self.quirks.vip_timing {
+            self.vip_cycles(instruction) as i64
+        } else {
+            1
+        };
+
+        // Dxyn waits for the next vblank interrupt on the VIP, so nothing else
+        // runs in the frame once a sprite is drawn.
+        if self.quirks.display_wait && matches!(instruction, Instruction::Draw { .. }) {
+            self.cycle_budget = self.cycle_budget.min(0);
+        }
+
+        let result = self.execute_instruction(instruction);
+        if result.is_err() {
+            // Leave the PC on the faulting instruction so it can be inspected.
+            self.pc = pc;
+        } else if let Some((opcode, before)) = before {
+            let after = self.snapshot();
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(pc, opcode, instruction, before, after);
+            }
+        }
+        result
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            v_registers: self.v_registers,
+            index_register: self.index_register,
+        }
+    }
+
+    // Starts a frame of `instructions` instructions or, with the `vip_timing`
+    // quirk, of the machine cycles a VIP has left once the vblank interrupt is
+    // done. Cycles the last instruction of a frame ran over by are taken from
+    // the next one.
+    pub fn start_frame(&mut self, instructions: u32) {
+        if self.quirks.vip_timing {
+            let budget = (CYCLES_PER_FRAME - VBLANK_CYCLES) as i64;
+            self.cycle_budget = self.cycle_budget.clamp(-budget, 0) + budget;
+        } else {
+            self.cycle_budget = instructions as i64;
+        }
+    }
+
+    // Whether the frame begun with `start_frame` has no time left.
+    pub fn frame_over(&self) -> bool {
+        self.cycle_budget <= 0
+    }
+
+    // Runs one 60Hz frame: `instructions` cycles (see `start_frame`), or up to
+    // the first sprite drawn with the `display_wait` quirk, then ticks the timers.
+    pub fn run_frame<T: AudioSink + ?Sized>(
+        &mut self,
+        instructions: u32,
+        audio_device: &mut T,
+    ) -> Result<StepOutcome, EmulatorError> {
+        let mut outcome = StepOutcome::Executed;
+
+        self.start_frame(instructions);
+        while !self.frame_over() {
+            outcome = self.execution_cycle()?;
+
+            if outcome == StepOutcome::Exited {
+                break;
+            }
+        }
+
+        if outcome != StepOutcome::Exited {
+            self.tick_timers(audio_device);
+        }
+
+        Ok(outcome)
+    }
+
+    // The instruction at `address`, decoded once and then reused until that memory changes.
+    fn decode(&mut self, address: u16) -> Instruction {
+        let index = address as usize % self.decoded.len();
+
+        match self.decoded[index] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = self.instruction_at(address);
+                self.decoded[index] = Some(instruction);
+                instruction
+            }
+        }
+    }
+
+    // Decodes the instruction at `address` without going through the cache, for tooling.
+    pub fn instruction_at(&self, address: u16) -> Instruction {
+        Instruction::decode(
+            self.read_word(address),
+            self.read_word(address.wrapping_add(2)),
+        )
+    }
+
+    // Big-endian word at `address`, wrapping around the end of memory.
+    pub fn read_word(&self, address: u16) -> u16 {
+        let address = address as usize % self.memory.len();
+        ((self.memory[address] as u16) << 8)
+            | (self.memory[(address + 1) % self.memory.len()] as u16)
+    }
+
+    pub fn execute_instruction(
+        &mut self,
+        instruction: Instruction,
+    ) -> Result<StepOutcome, EmulatorError> {
+        let mut outcome = StepOutcome::Executed;
+
+        match instruction {
+            Instruction::ScrollDown { n } => {
+                // 00Cn:
+                // Scroll the display down by n lines.
+                self.scroll_down(n as usize);
+            }
+            Instruction::Clear => {
+                // 00E0:
+                // Clears display.
+                self.clear_display();
+            }
+            Instruction::Return => {
+                // 00EE:
+                // Return from a subroutine.
+                self.pc = self.stack.pop().ok_or(EmulatorError::StackUnderflow)?;
+            }
+            Instruction::ScrollRight => {
+                // 00FB:
+                // Scroll the display right by 4 pixels.
+                self.scroll_horizontally(4);
+            }
+            Instruction::ScrollLeft => {
+                // 00FC:
+                // Scroll the display left by 4 pixels.
+                self.scroll_horizontally(-4);
+            }
+            Instruction::Exit => {
+                // 00FD:
+                // Exit the interpreter.
+                self.exited = true;
+                outcome = StepOutcome::Exited;
+            }
+            Instruction::LowRes => {
+                // 00FE:
+                // Switch to low-res (64x32) mode.
+                self.set_hires(false);
+            }
+            Instruction::HighRes => {
+                // 00FF:
+                // Switch to hi-res (128x64) mode.
+                self.set_hires(true);
+            }
+            Instruction::MachineCall { .. } => {
+                // 0nnn:
+                // Jump to a machine code routine at nnn. Only the original hardware could run
+                // those, so like most interpreters we ignore it.
+            }
+            Instruction::Jump { address } => {
+                // 1nnn:
+                // Jump to address *nnn*
+                self.pc = address;
+            }
+            Instruction::Call { address } => {
+                // 2nnn:
+                // The interpreter increments the stack pointer, then puts the current PC on the top of the stack. The PC is then set to *nnn*.
+                if self.stack.len() >= STACK_SIZE {
+                    return Err(EmulatorError::StackOverflow);
+                }
+                self.stack.push(self.pc);
+                self.pc = address;
+            }
+            Instruction::SkipIfEqual { x, byte } => {
+                // 3xkk:
+                // Skip next instruction if Vx == kk (Vx: register number *x*).
+
+                self.skip_next_instruction_if(self.v_registers[x as usize] == byte);
+            }
+            Instruction::SkipIfNotEqual { x, byte } => {
+                // 4xkk:
+                // Skip next instruction if Vx != kk.
+
+                self.skip_next_instruction_if(self.v_registers[x as usize] != byte);
+            }
+            Instruction::SaveRange { x, y } => {
+                // 5xy2:
+                // Store registers Vx through Vy in memory starting at location I, leaving I unchanged.
+                // x may be greater than y, in which case the registers are stored in reverse order.
+                let range =
+                    self.memory_range(self.index_register as usize, x.abs_diff(y) as usize + 1)?;
+                self.record_write(range.clone());
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.memory[address] = self.v_registers[register];
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                // 5xy3:
+                // Read registers Vx through Vy from memory starting at location I, leaving I unchanged.
+                let range =
+                    self.memory_range(self.index_register as usize, x.abs_diff(y) as usize + 1)?;
+                for (address, register) in range.zip(register_range(x, y)) {
+                    self.v_registers[register] = self.memory[address];
+                }
+            }
+            Instruction::SkipIfRegistersEqual { x, y } => {
+                // 5xy0:
+                // Skip next instruction if Vx == Vy.
+
+                self.skip_next_instruction_if(
+                    self.v_registers[x as usize] == self.v_registers[y as usize],
+                );
+            }
+            Instruction::Set { x, byte } => {
+                // 6xkk:
+                // The interpreter puts the value kk into register Vx.
+                self.v_registers[x as usize] = byte;
+            }
+            Instruction::Add { x, byte } => {
+                // 7xkk:
+                // Adds the value kk to the value of register Vx, then stores the result in Vx.
+                self.v_registers[x as usize] = self.v_registers[x as usize].wrapping_add(byte);
+            }
+            Instruction::Move { x, y } => {
+                // 8xy0:
+                // Set Vx = Vy.
+                self.v_registers[x as usize] = self.v_registers[y as usize];
+            }
+            Instruction::Or { x, y } => {
+                // 8xy1:
+                // Set Vx = Vx OR Vy.
+                self.v_registers[x as usize] |= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_registers[0xF] = 0
+                }
+            }
+            Instruction::And { x, y } => {
+                // 8xy2:
+                // Set Vx = Vx AND Vy.
+                self.v_registers[x as usize] &= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_registers[0xF] = 0
+                }
+            }
+            Instruction::Xor { x, y } => {
+                // 8xy3:
+                // Set Vx = Vx XOR Vy.
+                self.v_registers[x as usize] ^= self.v_registers[y as usize];
+                if self.quirks.vf_reset {
+                    self.v_registers[0xF] = 0
+                }
+            }
+            Instruction::AddRegisters { x, y } => {
+                // 8xy4:
+                // Set Vx = Vx + Vy, set VF = carry.
+                let (sum, overflow) =
+                    self.v_registers[x as usize].overflowing_add(self.v_registers[y as usize]);
+                self.v_registers[x as usize] = sum;
+                self.v_registers[0xF] = if overflow { 1 } else { 0 };
+            }
+            Instruction::Sub { x, y } => {
+                // 8xy5:
+                // Set Vx = Vx - Vy, set VF = NOT borrow.
+                let (diff, overflow) =
+                    self.v_registers[x as usize].overflowing_sub(self.v_registers[y as usize]);
+                self.v_registers[x as usize] = diff;
+                self.v_registers[0xF] = if overflow { 0 } else { 1 };
+            }
+            Instruction::ShiftRight { x, y } => {
+                // 8xy6:
+                // Set Vx = Vy SHR 1 (Vx SHR 1 with the shift quirk), set VF = the bit shifted out.
+                let source = self.shift_source(x, y);
+                self.v_registers[x as usize] = source >> 1;
+                self.v_registers[0xF] = source & 1;
+            }
+            Instruction::SubReversed { x, y } => {
+                // 8xy7:
+                // Set Vx = Vy - Vx, set VF = NOT borrow.
+                let (diff, overflow) =
+                    self.v_registers[y as usize].overflowing_sub(self.v_registers[x as usize]);
+                self.v_registers[x as usize] = diff;
+                self.v_registers[0xF] = if overflow { 0 } else { 1 };
+            }
+            Instruction::ShiftLeft { x, y } => {
+                // 8xyE:
+                // Set Vx = Vy SHL 1 (Vx SHL 1 with the shift quirk), set VF = the bit shifted out.
+                let source = self.shift_source(x, y);
+                self.v_registers[x as usize] = source << 1;
+                self.v_registers[0xF] = if (source & 0x80) == 0x80 { 1 } else { 0 };
+            }
+            Instruction::SkipIfRegistersNotEqual { x, y } => {
+                // 9xy0:
+                // Skip next instruction if Vx != Vy.
+
+                self.skip_next_instruction_if(
+                    self.v_registers[x as usize] != self.v_registers[y as usize],
+                );
+            }
+            Instruction::SetIndex { address } => {
+                // Annn:
+                // The value of register I is set to nnn.
+
+                self.index_register = address;
+            }
+            Instruction::JumpOffset { x, address } => {
+                // Bnnn:
+                // The program counter is set to nnn plus the value of V0.
+                // With the jump quirk this reads as Bxnn, and Vx is used instead of V0.
+
+                let offset_register = if self.quirks.jump_vx { x as usize } else { 0 };
+                self.pc = address + self.v_registers[offset_register] as u16;
+            }
+            Instruction::Random { x, byte } => {
+                // Cxkk:
+                // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx.
+
+                let random_byte = if self.quirks.timed_rng {
+                    self.rng.next_timed_byte()
+                } else {
+                    self.rng.next_byte()
+                };
+
+                self.v_registers[x as usize] = random_byte & byte;
+            }
+            Instruction::Draw { x, y, n } => {
+                // Dxyn:
+                // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+                // Dxy0 draws a 16x16 sprite made of 32 bytes instead.
+                self.update_sprite(n as usize, x as usize, y as usize)?;
+                self.draw_flag = true;
+            }
+            Instruction::SkipIfKey { x } => {
+                // Ex9E
+                // Skip next instruction if key with the value of Vx is pressed.
+                let vx = self.v_registers[x as usize] as usize & 0xF;
+                self.skip_next_instruction_if(self.btn_pressings[vx]);
+            }
+            Instruction::SkipIfNotKey { x } => {
+                // ExA1
+                // Skip next instruction if key with the value of Vx is NOT pressed.
+                let vx = self.v_registers[x as usize] as usize & 0xF;
+                self.skip_next_instruction_if(!self.btn_pressings[vx]);
+            }
+            Instruction::SetIndexLong { address } => {
+                // F000 nnnn:
+                // Set I = nnnn, the 16-bit address stored right after this instruction.
+                self.index_register = address;
+                self.pc = self.pc.wrapping_add(2);
+            }
+            Instruction::SelectPlanes { n } => {
+                // Fn01:
+                // Select the drawing planes given by the bitmask n.
+                self.selected_planes = n & 0b11;
+            }
+            Instruction::LoadAudio => {
+                // F002:
+                // Load the 16-byte audio pattern buffer from memory starting at location I.
+                let range = self.memory_range(self.index_register as usize, AUDIO_PATTERN_SIZE)?;
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                pattern.copy_from_slice(&self.memory[range]);
+
+                self.audio_pattern = Some(pattern);
+                self.audio_changed = true;
+            }
+            Instruction::GetDelay { x } => {
+                // Fx07
+                //Set Vx = delay timer value.
+                self.v_registers[x as usize] = self.delay_timer;
+            }
+            Instruction::WaitKey { x } => {
+                // Fx0A
+                // Wait for a key press, store the value of the key in Vx.
+
+                let mut pressed = false;
+
+                if let Some(btn) = self.btn_waiting_for_release {
+                    if !self.btn_pressings[btn as usize] {
+                        pressed = true;
+                        self.v_registers[x as usize] = btn;
+                        self.btn_waiting_for_release = None;
+                    }
+                } else {
+                    for (i, is_btn_pressed) in self.btn_pressings.iter().enumerate() {
+                        if *is_btn_pressed {
+                            self.btn_waiting_for_release = Some(i as u8);
+                        }
+                    }
+                }
+
+                if !pressed {
+                    self.pc = self.pc.wrapping_sub(2);
+                    outcome = StepOutcome::WaitingForKey;
+                }
+            }
+            Instruction::SetDelay { x } => {
+                // Fx15
+                // Set delay timer = Vx.
+                self.delay_timer = self.v_registers[x as usize];
+            }
+            Instruction::SetSound { x } => {
+                // Fx18
+                // Set delay timer = Vx.
+                self.sound_timer = self.v_registers[x as usize];
+            }
+            Instruction::AddIndex { x } => {
+                // Fx1E
+                // Set I = I + Vx.
+                self.index_register = self
+                    .index_register
+                    .wrapping_add(self.v_registers[x as usize] as u16);
+            }
+            Instruction::Font { x } => {
+                // Fx29
+                // Set I = location of sprite for digit Vx.
+                let vx = self.v_registers[x as usize] & 0xF;
+                let sprite_address = FONTSET_START_ADDRESS + (vx as usize * 5);
+                self.index_register = sprite_address as u16;
+            }
+            Instruction::BigFont { x } => {
+                // Fx30
+                // Set I = location of the 10-byte big font sprite for digit Vx.
+                let vx = self.v_registers[x as usize] & 0xF;
+                let sprite_address = BIG_FONTSET_START_ADDRESS + (vx as usize * 10);
+                self.index_register = sprite_address as u16;
+            }
+            Instruction::SetPitch { x } => {
+                // Fx3A
+                // Set the audio pattern playback pitch = Vx.
+                self.pitch = self.v_registers[x as usize];
+                self.audio_changed = true;
+            }
+            Instruction::Bcd { x } => {
+                // Fx33:
+                // Store Binary-Coded Decimal representation of Vx in memory locations I, I+1, and I+2.
+
+                let range = self.memory_range(self.index_register as usize, 3)?;
+                let vx = self.v_registers[x as usize];
+
+                let hundreds = vx / 100;
+                let tens = (vx % 100) / 10;
+                let units = vx % 10;
+
+                self.record_write(range.clone());
+                self.memory[range].copy_from_slice(&[hundreds, tens, units]);
+            }
+            Instruction::Save { x } => {
+                // Fx55
+                // Store registers V0 through Vx in memory starting at location I.
+                let range = self.memory_range(self.index_register as usize, x as usize + 1)?;
+                self.record_write(range.clone());
+                self.memory[range].copy_from_slice(&self.v_registers[0..=(x as usize)]);
+
+                self.increment_index_after_load_store(x as u16);
+            }
+            Instruction::Load { x } => {
+                // Fx65
+                // Read registers V0 through Vx from memory starting at location I.
+                let range = self.memory_range(self.index_register as usize, x as usize + 1)?;
+                self.v_registers[0..=(x as usize)].copy_from_slice(&self.memory[range]);
+
+                self.increment_index_after_load_store(x as u16);
+            }
+            Instruction::SaveFlags { x } => {
+                // Fx75
+                // Store registers V0 through Vx in the RPL user flags.
+                let x = x as usize;
+                self.rpl_flags[0..=x].copy_from_slice(&self.v_registers[0..=x]);
+            }
+            Instruction::LoadFlags { x } => {
+                // Fx85
+                // Read registers V0 through Vx from the RPL user flags.
+                let x = x as usize;
+                self.v_registers[0..=x].copy_from_slice(&self.rpl_flags[0..=x]);
+            }
+            Instruction::Unknown { opcode } => {
+                return Err(EmulatorError::InvalidOpcode {
+                    pc: self.pc.wrapping_sub(2),
+                    opcode,
+                });
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    // The register 8xy6 and 8xyE shift.
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shift_vx {
+            self.v_registers[x as usize]
+        } else {
+            self.v_registers[y as usize]
+        }
+    }
+
+    // Range of `len` bytes of memory starting at `start`, provided all of it exists.
+    fn memory_range(&self, start: usize, len: usize) -> Result<Range<usize>, EmulatorError> {
+        if start + len > self.memory.len() {
+            return Err(EmulatorError::MemoryOutOfBounds {
+                addr: start.max(self.memory.len()),
+            });
+        }
+        Ok(start..(start + len))
+    }
+
+    // Must be called before writing to `range`, while the instruction doing it is still in memory.
+    fn record_write(&mut self, range: Range<usize>) {
+        let pc = self.pc.wrapping_sub(2);
+        self.last_write = Some(MemoryWrite {
+            pc,
+            instruction: self.instruction_at(pc),
+            range: range.clone(),
+        });
+        self.invalidate_decoded(range);
+    }
+
+    // Forgets the decoded instructions that overlap `range`, which is about to change.
+    fn invalidate_decoded(&mut self, range: Range<usize>) {
+        // An instruction is up to 4 bytes long, so the 3 addresses before the range can start one
+        // that overlaps it. Those before address 0 wrap around to the end of memory.
+        let len = self.decoded.len();
+        let start = range.start.saturating_sub(3);
+        self.decoded[start..range.end.min(len)].fill(None);
+        if range.start < 3 {
+            self.decoded[(len - (3 - range.start))..].fill(None);
+        }
+    }
+
+    // Clears the selected planes only.
+    fn clear_display(&mut self) {
+        for line in self.display.iter_mut() {
+            for pixel in line.iter_mut() {
+                *pixel &= !self.selected_planes;
+            }
+        }
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        if self.quirks.resolution_clear {
+            self.display.fill([0; HIRES_SCREEN_WIDTH]);
+        }
+    }
+
+    // Scrolling, like clearing, only moves the pixels of the selected planes.
+    fn scroll_down(&mut self, lines: usize) {
+        let (width, height) = self.screen_size();
+        let planes = self.selected_planes;
+
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let source = if y >= lines {
+                    self.display[y - lines][x]
+                } else {
+                    0
+                };
+                self.display[y][x] = (self.display[y][x] & !planes) | (source & planes);
+            }
+        }
+    }
+
+    // Positive amounts scroll right, negative ones scroll left.
+    fn scroll_horizontally(&mut self, amount: isize) {
+        let (width, height) = self.screen_size();
+        let planes = self.selected_planes;
+        let shift = amount.unsigned_abs();
+
+        for line in self.display[0..height].iter_mut() {
+            let line = &mut line[0..width];
+
+            if amount > 0 {
+                for x in (0..width).rev() {
+                    let source = if x >= shift { line[x - shift] } else { 0 };
+                    line[x] = (line[x] & !planes) | (source & planes);
+                }
+            } else {
+                for x in 0..width {
+                    let source = if x + shift < width {
+                        line[x + shift]
+                    } else {
+                        0
+                    };
+                    line[x] = (line[x] & !planes) | (source & planes);
+                }
+            }
+        }
+    }
+
+    fn skip_next_instruction_if(&mut self, condition: bool) {
+        if condition {
+            // F000 nnnn is twice as long as any other instruction, so it must be skipped whole.
+            let next_size = if self.read_word(self.pc) == 0xF000 {
+                4
+            } else {
+                2
+            };
+            self.pc = self.pc.wrapping_add(next_size);
+        }
+    }
+
+    fn increment_index_after_load_store(&mut self, x: u16) {
+        match self.quirks.index_increment {
+            IndexIncrement::None => {}
+            IndexIncrement::X => self.index_register = self.index_register.wrapping_add(x),
+            IndexIncrement::XPlusOne => {
+                self.index_register = self.index_register.wrapping_add(x + 1)
+            }
+        }
+    }
+
+    fn update_sprite(
+        &mut self,
+        sprite_height: usize,
+        x: usize,
+        y: usize,
+    ) -> Result<(), EmulatorError> {
+        let (screen_width, screen_height) = self.screen_size();
+
+        let (sprite_width, sprite_height) = if sprite_height == 0 {
+            (16, 16)
+        } else {
+            (NUM_BITS_IN_BYTE, sprite_height)
+        };
+        let bytes_per_line = sprite_width / NUM_BITS_IN_BYTE;
+        let sprite_size = sprite_height * bytes_per_line;
+
+        let starting_x = (self.v_registers[x] as usize) % screen_width;
+        let starting_y = (self.v_registers[y] as usize) % screen_height;
+
+        let mut collided_lines = 0;
+
+        // When clipping, whatever goes past the edges is simply not drawn;
+        // otherwise it wraps around to the opposite side.
+        let (vertical_limit, horizontal_limit) = if self.quirks.clipping {
+            (
+                min(sprite_height, screen_height - starting_y),
+                min(sprite_width, screen_width - starting_x),
+            )
+        } else {
+            (sprite_height, sprite_width)
+        };
+
+        // Each selected plane gets its own sprite, stored one after the other starting at I.
+        let planes_count = self.selected_planes.count_ones() as usize;
+        let mut sprite_address = self
+            .memory_range(self.index_register as usize, planes_count * sprite_size)?
+            .start;
+
+        for plane in 0..NUM_PLANES {
+            let plane_bit = 1 << plane;
+            if self.selected_planes & plane_bit == 0 {
+                continue;
+            }
+
+            let sprite = &self.memory[sprite_address..(sprite_address + sprite_size)];
+            sprite_address += sprite_size;
+
+            for (i, sprite_line) in sprite
+                .chunks(bytes_per_line)
+                .enumerate()
+                .take(vertical_limit)
+            {
+                // first, we choose the **line** with *starting_y*
+                let display_line = &mut self.display[(starting_y + i) % screen_height];
+                let sprite_line = sprite_line.iter().fold(0_u16, |line, byte| {
+                    (line << NUM_BITS_IN_BYTE) | *byte as u16
+                });
+
+                let mut line_collided = false;
+
+                for j in 0..horizontal_limit {
+                    let bit = (1_u16 << (sprite_width - j - 1)) & sprite_line;
+
+                    if bit != 0 {
+                        // then, we choose the **column** with *starting_x*
+                        let line_index = (starting_x + j) % screen_width;
+
+                        if display_line[line_index] & plane_bit != 0 {
+                            line_collided = true;
+                        }
+
+                        display_line[line_index] ^= plane_bit;
+                    }
+                }
+
+                if line_collided {
+                    collided_lines += 1;
+                }
+            }
+        }
+
+        self.v_registers[0xF] = if self.quirks.count_collided_rows && self.hires {
+            // SUPER-CHIP also counts the lines that fell off the bottom of the screen.
+            (collided_lines + sprite_height - vertical_limit) as u8
+        } else if collided_lines > 0 {
+            1
+        } else {
+            0
+        };
+
+        Ok(())
+    }
+
+    pub fn tick_timers<T: AudioSink + ?Sized>(&mut self, audio_device: &mut T) {
+        self.rng.tick();
+
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.audio_changed {
+            audio_device.set_pattern(self.audio_pattern, self.pitch);
+            self.audio_changed = false;
+        }
+
+        if self.sound_timer > 0 {
+            audio_device.resume();
+            self.sound_timer -= 1;
+        } else {
+            audio_device.pause();
+        }
+
+        audio_device.tick();
+    }
+}
+
+// Registers from x to y, counting down when x > y.
+fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+    let (x, y) = (x as usize, y as usize);
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
+}
@@ -0,0 +1,194 @@
+use alloc::{format, string::String};
+use core::str::FromStr;
+
+// Platforms whose behaviour is known for every ambiguous opcode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" | "chip8" | "chip-8" => Ok(Platform::CosmacVip),
+            "chip48" | "chip-48" => Ok(Platform::Chip48),
+            "schip" | "superchip" | "super-chip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(format!("Unknown platform: {s}")),
+        }
+    }
+}
+
+// What Fx55 and Fx65 do to I once they are done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexIncrement {
+    // I is left untouched (SUPER-CHIP 1.1).
+    None,
+    // I = I + x (CHIP-48).
+    X,
+    // I = I + x + 1 (COSMAC VIP, XO-CHIP).
+    XPlusOne,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8xy1, 8xy2 and 8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    // 8xy6 and 8xyE shift Vx in place, ignoring Vy.
+    pub shift_vx: bool,
+    pub index_increment: IndexIncrement,
+    // Bnnn jumps to nnn + Vx (read as Bxnn) instead of nnn + V0.
+    pub jump_vx: bool,
+    // Sprites are cut at the screen edges instead of wrapping around.
+    pub clipping: bool,
+    // In hi-res mode, Dxyn sets VF to the number of sprite rows that collided
+    // or were clipped at the bottom, instead of just 0 or 1.
+    pub count_collided_rows: bool,
+    // XO-CHIP's 64KB address space instead of the usual 4KB.
+    pub extended_memory: bool,
+    // Cxkk draws numbers from a counter that timer ticks move on, see `Rng::next_timed_byte`.
+    pub timed_rng: bool,
+    // Frames run as many instructions as fit in the machine cycles of a COSMAC
+    // VIP frame, instead of a fixed number, see `Emulator::vip_cycles`.
+    pub vip_timing: bool,
+    // Dxyn waits for the vblank interrupt, so at most one sprite is drawn per frame.
+    pub display_wait: bool,
+    // 00FE and 00FF clear the display (XO-CHIP), instead of leaving it as it is (SUPER-CHIP 1.1).
+    pub resolution_clear: bool,
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        vf_reset: true,
+        shift_vx: false,
+        index_increment: IndexIncrement::XPlusOne,
+        jump_vx: false,
+        clipping: true,
+        count_collided_rows: false,
+        extended_memory: false,
+        timed_rng: false,
+        vip_timing: false,
+        display_wait: true,
+        resolution_clear: false,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: true,
+        index_increment: IndexIncrement::X,
+        jump_vx: true,
+        clipping: true,
+        count_collided_rows: false,
+        extended_memory: false,
+        timed_rng: false,
+        vip_timing: false,
+        display_wait: false,
+        resolution_clear: false,
+    };
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: true,
+        index_increment: IndexIncrement::None,
+        jump_vx: true,
+        clipping: true,
+        count_collided_rows: true,
+        extended_memory: false,
+        timed_rng: false,
+        vip_timing: false,
+        display_wait: false,
+        resolution_clear: false,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_vx: false,
+        index_increment: IndexIncrement::XPlusOne,
+        jump_vx: false,
+        clipping: false,
+        count_collided_rows: false,
+        extended_memory: true,
+        timed_rng: false,
+        vip_timing: false,
+        display_wait: false,
+        resolution_clear: true,
+    };
+
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::CosmacVip => Quirks::COSMAC_VIP,
+            Platform::Chip48 => Quirks::CHIP_48,
+            Platform::SuperChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    // Overrides a single flag by name, e.g. `set("clipping", "off")`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name == "index_increment" {
+            self.index_increment = match value {
+                "none" => IndexIncrement::None,
+                "x" => IndexIncrement::X,
+                "x+1" => IndexIncrement::XPlusOne,
+                _ => return Err(format!("Invalid value for index_increment: {value}")),
+            };
+            return Ok(());
+        }
+
+        let flag = match name {
+            "vf_reset" => &mut self.vf_reset,
+            "shift_vx" => &mut self.shift_vx,
+            "jump_vx" => &mut self.jump_vx,
+            "clipping" => &mut self.clipping,
+            "count_collided_rows" => &mut self.count_collided_rows,
+            "extended_memory" => &mut self.extended_memory,
+            "timed_rng" => &mut self.timed_rng,
+            "vip_timing" => &mut self.vip_timing,
+            "display_wait" => &mut self.display_wait,
+            "resolution_clear" => &mut self.resolution_clear,
+            _ => return Err(format!("Unknown quirk: {name}")),
+        };
+
+        *flag = match value {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return Err(format!("Invalid value for {name}: {value}")),
+        };
+
+        Ok(())
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::COSMAC_VIP
+    }
+}
+
+// Parses a preset name optionally followed by overrides, e.g.
+// `schip,clipping=off,index_increment=x+1`.
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let mut quirks = match parts.next() {
+            Some(preset) if !preset.contains('=') => Quirks::for_platform(preset.parse()?),
+            _ => return Err(format!("Missing platform preset in \"{s}\"")),
+        };
+
+        for part in parts {
+            let Some((name, value)) = part.split_once('=') else {
+                return Err(format!("Expected name=value, found \"{part}\""));
+            };
+            quirks.set(name.trim(), value.trim())?;
+        }
+
+        Ok(quirks)
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::{collections::VecDeque, vec::Vec};
+
+// Ring buffer of the last `depth` frames, used to step play back in time. A
+// depth of 0 records nothing, which turns rewinding off.
+//
+// Only the newest state is kept whole. Every older frame is stored as the XOR
+// between it and the frame after it, run-length encoded: since most of memory
+// and the display don't change from one frame to the next, these deltas are
+// mostly zeroes and shrink to a few bytes each.
+pub struct Rewind {
+    depth: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    pub fn new(depth: usize) -> Rewind {
+        Rewind {
+            depth,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Records the state of the frame that just ran.
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.depth == 0 {
+            return;
+        }
+
+        if let Some(latest) = self.latest.take() {
+            if latest.len() == state.len() {
+                if self.deltas.len() == self.depth {
+                    self.deltas.pop_front();
+                }
+                self.deltas.push_back(encode_delta(&latest, &state));
+            } else {
+                // States of different sizes can't be diffed, so history starts over.
+                self.deltas.clear();
+            }
+        }
+
+        self.latest = Some(state);
+    }
+
+    // Steps back one frame and returns its state, or None once the oldest frame is reached.
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        let latest = self.latest.as_mut()?;
+        apply_delta(latest, &delta);
+
+        Some(latest)
+    }
+}
+
+// Encodes `previous XOR current` as a sequence of (zero run, literal run, literal bytes),
+// with both run lengths written as LEB128 varints.
+fn encode_delta(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = previous.iter().zip(current).map(|(a, b)| a ^ b).collect();
+    let mut delta = Vec::new();
+    let mut i = 0;
+
+    while i < xor.len() {
+        let zeroes = xor[i..].iter().take_while(|byte| **byte == 0).count();
+        i += zeroes;
+        let literals = xor[i..].iter().take_while(|byte| **byte != 0).count();
+
+        write_varint(&mut delta, zeroes);
+        write_varint(&mut delta, literals);
+        delta.extend_from_slice(&xor[i..(i + literals)]);
+        i += literals;
+    }
+
+    delta
+}
+
+// XORs a delta made by `encode_delta` back into `state`, turning it into the other frame.
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut position = 0;
+    let mut delta = delta;
+
+    while !delta.is_empty() {
+        position += read_varint(&mut delta);
+        let literals = read_varint(&mut delta);
+
+        for (byte, xor) in state[position..(position + literals)].iter_mut().zip(delta) {
+            *byte ^= xor;
+        }
+        position += literals;
+        delta = &delta[literals..];
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+
+    while let Some((byte, rest)) = input.split_first() {
+        *input = rest;
+        value |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    value
+}
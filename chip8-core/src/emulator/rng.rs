@@ -0,0 +1,103 @@
+// The random number generator behind Cxkk. It is owned by the emulator and
+// fully saved in save states, so that a run started from the same seed always
+// draws the same numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+    // Counter behind `next_timed_byte`, moved on by every timer tick.
+    counter: u16,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            seed,
+            state: seed,
+            counter: seed as u16,
+        }
+    }
+
+    // A seed from the OS when available; a fixed one is all there is without `std`.
+    pub fn from_entropy() -> Rng {
+        #[cfg(feature = "std")]
+        {
+            use std::hash::{BuildHasher, RandomState};
+            Rng::new(RandomState::new().hash_one(0))
+        }
+
+        #[cfg(not(feature = "std"))]
+        Rng::new(0)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Any byte from 0 to 255 (SplitMix64).
+    pub fn next_byte(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+
+    // A byte from a counter that timer ticks also move on, so the numbers
+    // depend on when they are drawn: the counter moves on, and the entry of a
+    // fixed table picked by its low half is added to its high half, which is
+    // the result. Only the shape of this is the COSMAC VIP interpreter's: the
+    // VIP reads its table from the interpreter's own code, which isn't included
+    // here, so this does not emulate the VIP's generator and its numbers aren't
+    // the ones a VIP draws.
+    pub fn next_timed_byte(&mut self) -> u8 {
+        self.counter = self.counter.wrapping_add(1);
+        let [high, low] = self.counter.to_be_bytes();
+        let value = high.wrapping_add(TABLE[low as usize]);
+        self.counter = u16::from_be_bytes([value, low]);
+        value
+    }
+
+    // Called on every 60Hz timer tick.
+    pub fn tick(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+
+    pub(super) fn to_bytes(self) -> [u8; 18] {
+        let mut bytes = [0; 18];
+        bytes[..8].copy_from_slice(&self.seed.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.state.to_le_bytes());
+        bytes[16..].copy_from_slice(&self.counter.to_le_bytes());
+        bytes
+    }
+
+    pub(super) fn from_bytes(bytes: [u8; 18]) -> Rng {
+        let word = |range: core::ops::Range<usize>| {
+            let mut word = [0; 8];
+            word[..range.len()].copy_from_slice(&bytes[range]);
+            u64::from_le_bytes(word)
+        };
+
+        Rng {
+            seed: word(0..8),
+            state: word(8..16),
+            counter: word(16..18) as u16,
+        }
+    }
+}
+
+// The table `next_timed_byte` reads, filled by a fixed xorshift sequence in
+// place of the VIP interpreter's code.
+const TABLE: [u8; 256] = {
+    let mut page = [0; 256];
+    let mut x: u32 = 0x2545_F491;
+    let mut i = 0;
+    while i < page.len() {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        page[i] = (x >> 24) as u8;
+        i += 1;
+    }
+    page
+};
@@ -0,0 +1,244 @@
+use alloc::vec::Vec;
+use core::{error::Error, fmt};
+
+use crate::emulator::{
+    Emulator,
+    consts::{AUDIO_PATTERN_SIZE, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, NUM_PLANES, STACK_SIZE},
+    hash::to_hex,
+    rng::Rng,
+};
+
+// Save state layout (all numbers little-endian):
+//
+//   magic "C8SS", format version (u16), SHA-256 of the ROM (32 bytes),
+//   memory size (u32) followed by the memory itself, V0-VF, I (u16), PC (u16),
+//   stack depth (u8) and entries (u16 each), delay and sound timers,
+//   key waiting for release (0xFF if none), RPL flags (16 bytes),
+//   hi-res flag, selected planes, the whole 128x64 display (1 byte per pixel),
+//   audio pattern (presence flag + 16 bytes), pitch, exited flag, since
+//   version 2 the random number generator: seed and state (u64 each), then the counter
+//   (u16), and since version 3 the cycles left in the frame (i64).
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u16 = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    NotAState,
+    UnsupportedVersion(u16),
+    // The state was saved while running a different ROM.
+    RomMismatch { expected: [u8; 32], found: [u8; 32] },
+    // The state was saved with a different memory size (e.g. XO-CHIP vs. CHIP-8).
+    MemorySizeMismatch { expected: usize, found: usize },
+    Truncated,
+    // A field holds a value the emulator can't be in, e.g. a 17th button.
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "Not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version {version}")
+            }
+            StateError::RomMismatch { expected, found } => write!(
+                f,
+                "Save state belongs to ROM {}, but ROM {} is loaded",
+                to_hex(found),
+                to_hex(expected)
+            ),
+            StateError::MemorySizeMismatch { expected, found } => write!(
+                f,
+                "Save state has {found} bytes of memory, but the emulator has {expected}"
+            ),
+            StateError::Truncated => write!(f, "Save state is truncated"),
+            StateError::Corrupt(field) => write!(f, "Save state has an invalid {field}"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl Emulator {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state =
+            Vec::with_capacity(self.memory.len() + HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT + 256);
+
+        state.extend_from_slice(MAGIC);
+        state.extend_from_slice(&VERSION.to_le_bytes());
+        state.extend_from_slice(&self.rom_hash);
+
+        state.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.v_registers);
+        state.extend_from_slice(&self.index_register.to_le_bytes());
+        state.extend_from_slice(&self.pc.to_le_bytes());
+
+        state.push(self.stack.len() as u8);
+        for address in &self.stack {
+            state.extend_from_slice(&address.to_le_bytes());
+        }
+
+        state.push(self.delay_timer);
+        state.push(self.sound_timer);
+        state.push(self.btn_waiting_for_release.unwrap_or(0xFF));
+        state.extend_from_slice(&self.rpl_flags);
+
+        state.push(self.hires as u8);
+        state.push(self.selected_planes);
+        for line in &self.display {
+            state.extend_from_slice(line);
+        }
+
+        state.push(self.audio_pattern.is_some() as u8);
+        state.extend_from_slice(&self.audio_pattern.unwrap_or_default());
+        state.push(self.pitch);
+        state.push(self.exited as u8);
+        state.extend_from_slice(&self.rng.to_bytes());
+        state.extend_from_slice(&self.cycle_budget.to_le_bytes());
+
+        state
+    }
+
+    // Restores a state made by `save_state`. Nothing is changed if it fails.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader { data };
+
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(StateError::NotAState);
+        }
+
+        let version = reader.u16()?;
+        // Older states load fine, only leaving what they don't have as it is.
+        if !(1..=VERSION).contains(&version) {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let rom_hash: [u8; 32] = reader.array()?;
+        if rom_hash != self.rom_hash {
+            return Err(StateError::RomMismatch {
+                expected: self.rom_hash,
+                found: rom_hash,
+            });
+        }
+
+        let memory_size = reader.u32()? as usize;
+        if memory_size != self.memory.len() {
+            return Err(StateError::MemorySizeMismatch {
+                expected: self.memory.len(),
+                found: memory_size,
+            });
+        }
+
+        // Everything is read before touching the emulator, so a truncated
+        // state cannot leave it half-loaded.
+        let memory = reader.bytes(memory_size)?;
+        let v_registers = reader.array()?;
+        let index_register = reader.u16()?;
+        let pc = reader.u16()?;
+
+        let stack_depth = reader.u8()?;
+        if stack_depth as usize > STACK_SIZE {
+            return Err(StateError::Corrupt("stack depth"));
+        }
+        let mut stack = Vec::with_capacity(stack_depth as usize);
+        for _ in 0..stack_depth {
+            stack.push(reader.u16()?);
+        }
+
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let btn_waiting_for_release = reader.u8()?;
+        if !matches!(btn_waiting_for_release, 0..=0xF | 0xFF) {
+            return Err(StateError::Corrupt("button waiting for release"));
+        }
+        let rpl_flags = reader.array()?;
+
+        let hires = reader.u8()? != 0;
+        // Planes and pixels are bitmasks of the XO-CHIP planes.
+        let all_planes = (1 << NUM_PLANES) - 1;
+        let selected_planes = reader.u8()?;
+        if selected_planes > all_planes {
+            return Err(StateError::Corrupt("plane selection"));
+        }
+        let mut display = [[0; HIRES_SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT];
+        for line in display.iter_mut() {
+            *line = reader.array()?;
+            if line.iter().any(|pixel| *pixel > all_planes) {
+                return Err(StateError::Corrupt("pixel"));
+            }
+        }
+
+        let has_audio_pattern = reader.u8()? != 0;
+        let audio_pattern: [u8; AUDIO_PATTERN_SIZE] = reader.array()?;
+        let pitch = reader.u8()?;
+        let exited = reader.u8()? != 0;
+        let rng = match version {
+            1 => self.rng,
+            _ => Rng::from_bytes(reader.array()?),
+        };
+        let cycle_budget = match version {
+            1 | 2 => self.cycle_budget,
+            _ => i64::from_le_bytes(reader.array()?),
+        };
+
+        self.memory.copy_from_slice(memory);
+        self.decoded.fill(None);
+        self.v_registers = v_registers;
+        self.index_register = index_register;
+        self.pc = pc;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.btn_waiting_for_release = match btn_waiting_for_release {
+            0xFF => None,
+            btn => Some(btn),
+        };
+        self.rpl_flags = rpl_flags;
+        self.hires = hires;
+        self.selected_planes = selected_planes;
+        self.display = display;
+        self.audio_pattern = has_audio_pattern.then_some(audio_pattern);
+        self.pitch = pitch;
+        self.audio_changed = true;
+        self.exited = exited;
+        self.rng = rng;
+        self.cycle_budget = cycle_budget;
+        self.draw_flag = true;
+
+        Ok(())
+    }
+}
+
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{disasm::instruction::Instruction, emulator::Emulator};
+
+// The COSMAC VIP's CDP1802 runs at 1.76MHz and takes 8 clock cycles per
+// machine cycle, which makes 3668 machine cycles per 60Hz frame.
+pub const CYCLES_PER_FRAME: u32 = 3668;
+
+// Cycles of every frame lost to the vblank interrupt: the CDP1861 fetches the
+// 128 display lines by DMA (8 cycles each), then the interrupt routine counts
+// the timers down.
+pub const VBLANK_CYCLES: u32 = 128 * 8 + 29;
+
+// Cycles the interpreter spends fetching an instruction and jumping to its routine.
+const FETCH_CYCLES: u32 = 20;
+
+impl Emulator {
+    // Roughly how many machine cycles the VIP interpreter takes to run
+    // `instruction` from the current state. The figures are estimates worked
+    // out from the interpreter's routines; instructions the VIP doesn't have
+    // are charged the fetch alone.
+    pub(super) fn vip_cycles(&self, instruction: Instruction) -> u32 {
+        let v = |x: u8| self.v_registers[x as usize];
+        let skip = |condition: bool| if condition { 4 } else { 0 };
+
+        FETCH_CYCLES
+            + match instruction {
+                // The whole 256 bytes of the display are cleared one by one.
+                Instruction::Clear => 3 * 256,
+                Instruction::Return | Instruction::Jump { .. } => 10,
+                Instruction::Call { .. } => 26,
+                Instruction::SkipIfEqual { x, byte } => 10 + skip(v(x) == byte),
+                Instruction::SkipIfNotEqual { x, byte } => 10 + skip(v(x) != byte),
+                Instruction::SkipIfRegistersEqual { x, y } => 14 + skip(v(x) == v(y)),
+                Instruction::SkipIfRegistersNotEqual { x, y } => 14 + skip(v(x) != v(y)),
+                Instruction::Set { .. } => 6,
+                Instruction::Add { .. } => 10,
+                // 8xyn is run by writing the matching 1802 instruction into RAM.
+                Instruction::Move { .. }
+                | Instruction::Or { .. }
+                | Instruction::And { .. }
+                | Instruction::Xor { .. }
+                | Instruction::AddRegisters { .. }
+                | Instruction::Sub { .. }
+                | Instruction::ShiftRight { .. }
+                | Instruction::SubReversed { .. }
+                | Instruction::ShiftLeft { .. } => 44,
+                Instruction::SetIndex { .. } => 12,
+                Instruction::JumpOffset { .. } => 22,
+                Instruction::Random { .. } => 36,
+                // Every sprite row is shifted into place bit by bit before being
+                // XORed into the two display bytes it covers.
+                Instruction::Draw { x, n, .. } => 46 + n as u32 * (22 + 4 * (v(x) % 8) as u32),
+                Instruction::SkipIfKey { x } => 14 + skip(self.btn_pressings[v(x) as usize & 0xF]),
+                Instruction::SkipIfNotKey { x } => {
+                    14 + skip(!self.btn_pressings[v(x) as usize & 0xF])
+                }
+                Instruction::GetDelay { .. }
+                | Instruction::SetDelay { .. }
+                | Instruction::SetSound { .. } => 10,
+                // Charged on every poll while waiting.
+                Instruction::WaitKey { .. } => 18,
+                Instruction::AddIndex { .. } | Instruction::Font { .. } => 20,
+                // Digits are found by repeated subtraction.
+                Instruction::Bcd { x } => {
+                    let value = v(x) as u32;
+                    30 + 8 * (value / 100 + value / 10 % 10 + value % 10)
+                }
+                Instruction::Save { x } | Instruction::Load { x } => 14 + 8 * (x as u32 + 1),
+                _ => 0,
+            }
+    }
+}
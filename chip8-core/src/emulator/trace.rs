@@ -0,0 +1,91 @@
+use alloc::{boxed::Box, format, string::ToString};
+use core::{fmt::Write, ops::RangeInclusive};
+
+use crate::disasm::instruction::Instruction;
+
+// Receives the lines of an execution trace, without their line break.
+pub trait TraceSink {
+    fn write_line(&mut self, line: &str);
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> TraceSink for W {
+    fn write_line(&mut self, line: &str) {
+        // A trace is a debugging aid, so failing to write it doesn't stop the emulator.
+        let _ = writeln!(self, "{line}");
+    }
+}
+
+// Writes a line for every instruction run inside `range`, e.g.
+//
+// 0x0234  6A02      LD VA, 0x02           VA=02
+// 0x0236  A2F0      LD I, 0x2F0           I=02F0
+// 0x0238  D015      DRW V0, V1, 5         VF=00
+//
+// with the registers the instruction changed at the end, until `limit` bytes were written.
+pub struct Tracer {
+    sink: Box<dyn TraceSink>,
+    range: RangeInclusive<u16>,
+    limit: usize,
+    written: usize,
+}
+
+// The registers an instruction may change, as they were before it ran.
+#[derive(Clone, Copy)]
+pub(super) struct Snapshot {
+    pub v_registers: [u8; 16],
+    pub index_register: u16,
+}
+
+impl Tracer {
+    pub fn new(sink: Box<dyn TraceSink>, range: RangeInclusive<u16>, limit: usize) -> Tracer {
+        Tracer {
+            sink,
+            range,
+            limit,
+            written: 0,
+        }
+    }
+
+    // Whether the instruction at `pc` goes in the trace.
+    pub(super) fn traces(&self, pc: u16) -> bool {
+        self.written < self.limit && self.range.contains(&pc)
+    }
+
+    pub(super) fn record(
+        &mut self,
+        pc: u16,
+        opcode: u32,
+        instruction: Instruction,
+        before: Snapshot,
+        after: Snapshot,
+    ) {
+        let opcode = match instruction.size() {
+            4 => format!("{opcode:08X}"),
+            _ => format!("{:04X}", opcode >> 16),
+        };
+        let mut line = format!("0x{pc:04X}  {opcode:<8}  {:<20}", instruction.to_string());
+
+        for (i, (old, new)) in before
+            .v_registers
+            .iter()
+            .zip(&after.v_registers)
+            .enumerate()
+        {
+            if old != new {
+                let _ = write!(line, "  V{i:X}={new:02X}");
+            }
+        }
+        if before.index_register != after.index_register {
+            let _ = write!(line, "  I={:04X}", after.index_register);
+        }
+
+        let line = line.trim_end();
+        self.sink.write_line(line);
+        self.written += line.len() + 1;
+
+        if self.written >= self.limit {
+            self.sink.write_line("# trace size limit reached");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{
+    audio::AudioSink,
+    emulator::{
+        Emulator,
+        error::{EmulatorError, StepOutcome},
+    },
+    scheduler::Scheduler,
+};
+
+// Shows frames to the user.
+pub trait DisplaySink {
+    // Called after every frame; the visible pixels are the top-left
+    // `emulator.screen_size()` corner of `emulator.display`.
+    fn present(&mut self, emulator: &Emulator);
+}
+
+// Feeds button presses to the emulator.
+pub trait InputSource {
+    // Called before every frame, to report presses and releases with `Emulator::set_btn_press`.
+    fn poll(&mut self, emulator: &mut Emulator);
+}
+
+pub struct NullDisplay;
+
+impl DisplaySink for NullDisplay {
+    fn present(&mut self, _emulator: &Emulator) {}
+}
+
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll(&mut self, _emulator: &mut Emulator) {}
+}
+
+// Runs up to `frames` frames, polling input before and presenting the display
+// after each one. Each frame runs its share of the instructions per second, as
+// the window does, so that runs play out the same in both.
+pub fn run_frames<I, D, A>(
+    emulator: &mut Emulator,
+    frames: u64,
+    instructions_per_second: u32,
+    input: &mut I,
+    display: &mut D,
+    audio: &mut A,
+) -> Result<StepOutcome, EmulatorError>
+where
+    I: InputSource + ?Sized,
+    D: DisplaySink + ?Sized,
+    A: AudioSink + ?Sized,
+{
+    let mut outcome = StepOutcome::Executed;
+    let mut scheduler = Scheduler::new(instructions_per_second);
+
+    for _ in 0..frames {
+        input.poll(emulator);
+        outcome = emulator.run_frame(scheduler.frame_instructions(), audio)?;
+        display.present(emulator);
+        emulator.draw_flag = false;
+
+        if outcome == StepOutcome::Exited {
+            break;
+        }
+    }
+
+    Ok(outcome)
+}
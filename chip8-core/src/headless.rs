@@ -0,0 +1,73 @@
+use alloc::{string::String, vec, vec::Vec};
+
+use crate::audio::NullAudio;
+use crate::emulator::Emulator;
+use crate::emulator::error::EmulatorError;
+use crate::emulator::hash::{sha256, to_hex};
+use crate::frontend::{InputSource, NullDisplay, run_frames};
+use crate::png;
+
+// Runs `frames` frames without a window or sound, taking button presses from
+// `input` (`NullInput` for none).
+pub fn run<I: InputSource + ?Sized>(
+    emulator: &mut Emulator,
+    frames: u64,
+    instructions_per_second: u32,
+    input: &mut I,
+) -> Result<(), EmulatorError> {
+    run_frames(
+        emulator,
+        frames,
+        instructions_per_second,
+        input,
+        &mut NullDisplay,
+        &mut NullAudio,
+    )?;
+
+    Ok(())
+}
+
+// The visible part of the display as text, one character per pixel.
+pub fn display_to_text(emulator: &Emulator) -> String {
+    // Indexed by the bitmask of planes a pixel is lit on.
+    const PIXELS: [char; 4] = [' ', '█', '▒', '▓'];
+    let (width, height) = emulator.screen_size();
+
+    let mut text = String::with_capacity((width + 1) * height * 3);
+    for line in emulator.display.iter().take(height) {
+        text.extend(line.iter().take(width).map(|pixel| PIXELS[*pixel as usize]));
+        text.push('\n');
+    }
+    text
+}
+
+// The visible pixels, one byte each holding the bitmask of planes they are lit on.
+fn visible_pixels(emulator: &Emulator) -> Vec<u8> {
+    let (width, height) = emulator.screen_size();
+    emulator
+        .display
+        .iter()
+        .take(height)
+        .flat_map(|line| line.iter().take(width).copied())
+        .collect()
+}
+
+// SHA-256 of the screen's width and height (one byte each) followed by its visible pixels,
+// so identical pictures in different resolutions don't collide.
+pub fn display_hash(emulator: &Emulator) -> String {
+    let (width, height) = emulator.screen_size();
+    let mut data = vec![width as u8, height as u8];
+    data.extend(visible_pixels(emulator));
+
+    to_hex(&sha256(&data))
+}
+
+pub fn display_to_png(emulator: &Emulator, palette: &[(u8, u8, u8); 4]) -> Vec<u8> {
+    let (width, height) = emulator.screen_size();
+    let pixels: Vec<(u8, u8, u8)> = visible_pixels(emulator)
+        .iter()
+        .map(|pixel| palette[*pixel as usize])
+        .collect();
+
+    png::encode_rgb(width as u32, height as u32, &pixels)
+}
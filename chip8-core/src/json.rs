@@ -0,0 +1,180 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{iter::Peekable, str::Chars};
+
+// How deep arrays and objects may nest, so that a file of `[[[[...` can't
+// overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+// Just enough JSON for the symbol and trace files other tools write.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // Members are kept in the order they were written.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // Whole numbers, also accepted as strings such as "0x2A0" or "672".
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && (*n as u64) as f64 == *n => Some(*n as u64),
+            Value::String(s) => parse_integer(s),
+            _ => None,
+        }
+    }
+}
+
+// Decimal, or hex with a `0x` prefix.
+pub fn parse_integer(s: &str) -> Option<u64> {
+    let s = s.trim();
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut chars = text.chars().peekable();
+    let value = parse_value(&mut chars, 0)?;
+
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(format!("Unexpected '{c}' after the end of the JSON value")),
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>, depth: usize) -> Result<Value, String> {
+    skip_whitespace(chars);
+    if depth > MAX_DEPTH {
+        return Err(format!("JSON nested deeper than {MAX_DEPTH} levels"));
+    }
+
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut members = vec![];
+            if consume(chars, '}') {
+                return Ok(Value::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                let name = parse_string(chars)?;
+                expect(chars, ':')?;
+                members.push((name, parse_value(chars, depth + 1)?));
+                if !consume(chars, ',') {
+                    expect(chars, '}')?;
+                    return Ok(Value::Object(members));
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = vec![];
+            if consume(chars, ']') {
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars, depth + 1)?);
+                if !consume(chars, ',') {
+                    expect(chars, ']')?;
+                    return Ok(Value::Array(items));
+                }
+            }
+        }
+        Some('"') => parse_string(chars).map(Value::String),
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+
+            match word.as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => word
+                    .parse()
+                    .map(Value::Number)
+                    .map_err(|_| format!("Invalid JSON value: {word}")),
+            }
+        }
+        None => Err(String::from("Unexpected end of JSON")),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err(String::from("Expected a JSON string"));
+    }
+
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                Some('r') => s.push('\r'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER);
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+                None => break,
+            },
+            Some(c) => s.push(c),
+            None => break,
+        }
+    }
+
+    Err(String::from("Unterminated JSON string"))
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn consume(chars: &mut Peekable<Chars>, expected: char) -> bool {
+    skip_whitespace(chars);
+    chars.next_if_eq(&expected).is_some()
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<(), String> {
+    if consume(chars, expected) {
+        Ok(())
+    } else {
+        Err(format!("Expected '{expected}' in JSON"))
+    }
+}
@@ -0,0 +1,23 @@
+//! SDL-free CHIP-8, SUPER-CHIP and XO-CHIP interpreter.
+//!
+//! Frontends drive an [`emulator::Emulator`] one frame at a time and plug in
+//! their own display, input and sound through the traits in [`frontend`] and
+//! [`audio`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod asm;
+pub mod audio;
+pub mod compare;
+pub mod debugger;
+pub mod disasm;
+pub mod emulator;
+pub mod frontend;
+pub mod headless;
+pub mod json;
+pub mod movie;
+pub mod png;
+pub mod scheduler;
+pub mod symbols;
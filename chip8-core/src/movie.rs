@@ -0,0 +1,299 @@
+use alloc::vec::Vec;
+use core::{error::Error, fmt};
+
+use crate::{
+    emulator::{
+        Emulator,
+        hash::to_hex,
+        quirks::{IndexIncrement, Quirks},
+    },
+    frontend::InputSource,
+};
+
+// Movie layout (all numbers little-endian):
+//
+//   magic "C8MV", format version (u16), SHA-256 of the ROM (32 bytes),
+//   quirks (u16 of flags, then the index increment), RNG seed (u64),
+//   instructions per second (u32), length in frames (u64),
+//   event count (u32) followed by the events: frame (u64), button, pressed flag.
+//
+// Quirk flags, from bit 0: vf_reset, shift_vx, jump_vx, clipping,
+// count_collided_rows, extended_memory, timed_rng, vip_timing, display_wait,
+// resolution_clear.
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u16 = 3;
+
+// The button presses and releases of a run, frame by frame, along with all it
+// takes to run the same ROM the same way again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: [u8; 32],
+    pub quirks: Quirks,
+    pub seed: u64,
+    pub instructions_per_second: u32,
+    pub frames: u64,
+    pub events: Vec<MovieEvent>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieEvent {
+    // Number of the frame the button changed before, from 0.
+    pub frame: u64,
+    pub button: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    NotAMovie,
+    UnsupportedVersion(u16),
+    // The movie was recorded while running a different ROM.
+    RomMismatch { expected: [u8; 32], found: [u8; 32] },
+    Truncated,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::NotAMovie => write!(f, "Not a movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported movie version {version}")
+            }
+            MovieError::RomMismatch { expected, found } => write!(
+                f,
+                "Movie was recorded with ROM {}, but ROM {} is loaded",
+                to_hex(found),
+                to_hex(expected)
+            ),
+            MovieError::Truncated => write!(f, "Movie is truncated"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64 + self.events.len() * 10);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.rom_hash);
+        bytes.extend_from_slice(&quirk_flags(&self.quirks).to_le_bytes());
+        bytes.push(match self.quirks.index_increment {
+            IndexIncrement::None => 0,
+            IndexIncrement::X => 1,
+            IndexIncrement::XPlusOne => 2,
+        });
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&self.instructions_per_second.to_le_bytes());
+        bytes.extend_from_slice(&self.frames.to_le_bytes());
+
+        bytes.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+        for event in &self.events {
+            bytes.extend_from_slice(&event.frame.to_le_bytes());
+            bytes.push(event.button);
+            bytes.push(event.pressed as u8);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(mut data: &[u8]) -> Result<Movie, MovieError> {
+        let mut take = |len: usize| -> Result<&[u8], MovieError> {
+            if data.len() < len {
+                return Err(MovieError::Truncated);
+            }
+            let (bytes, rest) = data.split_at(len);
+            data = rest;
+            Ok(bytes)
+        };
+
+        if take(MAGIC.len())? != MAGIC {
+            return Err(MovieError::NotAMovie);
+        }
+        let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        if !(1..=VERSION).contains(&version) {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let rom_hash = take(32)?.try_into().unwrap();
+        let flags = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        let index_increment = match take(1)?[0] {
+            0 => IndexIncrement::None,
+            1 => IndexIncrement::X,
+            _ => IndexIncrement::XPlusOne,
+        };
+        let seed = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let instructions_per_second = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let frames = u64::from_le_bytes(take(8)?.try_into().unwrap());
+
+        let event_count = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        let mut events = Vec::new();
+        for _ in 0..event_count {
+            let event = take(10)?;
+            events.push(MovieEvent {
+                frame: u64::from_le_bytes(event[..8].try_into().unwrap()),
+                button: event[8],
+                pressed: event[9] != 0,
+            });
+        }
+
+        let flag = |bit: u16| flags & (1 << bit) != 0;
+        let quirks = Quirks {
+            vf_reset: flag(0),
+            shift_vx: flag(1),
+            index_increment,
+            jump_vx: flag(2),
+            clipping: flag(3),
+            count_collided_rows: flag(4),
+            extended_memory: flag(5),
+            timed_rng: flag(6),
+            vip_timing: flag(7),
+            // Sprites always ended the frame before version 2 had the flag.
+            display_wait: version == 1 || flag(8),
+            // As did switching resolutions clear the screen before version 3.
+            resolution_clear: version < 3 || flag(9),
+        };
+
+        Ok(Movie {
+            rom_hash,
+            quirks,
+            seed,
+            instructions_per_second,
+            frames,
+            events,
+        })
+    }
+}
+
+fn quirk_flags(quirks: &Quirks) -> u16 {
+    [
+        quirks.vf_reset,
+        quirks.shift_vx,
+        quirks.jump_vx,
+        quirks.clipping,
+        quirks.count_collided_rows,
+        quirks.extended_memory,
+        quirks.timed_rng,
+        quirks.vip_timing,
+        quirks.display_wait,
+        quirks.resolution_clear,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |flags, (bit, on)| flags | ((*on as u16) << bit))
+}
+
+// Records the buttons as they are at the start of every frame, whichever way
+// the frontend set them. Polled right before each frame is run.
+pub struct Recorder {
+    movie: Movie,
+    buttons: [bool; 16],
+}
+
+impl Recorder {
+    // Starts a movie of the ROM loaded in `emulator`, from its current state.
+    pub fn new(emulator: &Emulator, instructions_per_second: u32) -> Recorder {
+        Recorder {
+            movie: Movie {
+                rom_hash: *emulator.rom_hash(),
+                quirks: *emulator.quirks(),
+                seed: emulator.rng_seed(),
+                instructions_per_second,
+                frames: 0,
+                events: Vec::new(),
+            },
+            buttons: *emulator.buttons(),
+        }
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+impl InputSource for Recorder {
+    fn poll(&mut self, emulator: &mut Emulator) {
+        for (button, (recorded, &pressed)) in
+            self.buttons.iter_mut().zip(emulator.buttons()).enumerate()
+        {
+            if *recorded != pressed {
+                *recorded = pressed;
+                self.movie.events.push(MovieEvent {
+                    frame: self.movie.frames,
+                    button: button as u8,
+                    pressed,
+                });
+            }
+        }
+
+        self.movie.frames += 1;
+    }
+}
+
+// Presses and releases the buttons of a movie. Polled right before each frame
+// is run, like any other input source.
+pub struct Player {
+    movie: Movie,
+    frame: u64,
+    next_event: usize,
+}
+
+impl Player {
+    // The emulator must have been made with the quirks and seed of the movie.
+    pub fn new(movie: Movie, emulator: &Emulator) -> Result<Player, MovieError> {
+        if movie.rom_hash != *emulator.rom_hash() {
+            return Err(MovieError::RomMismatch {
+                expected: *emulator.rom_hash(),
+                found: movie.rom_hash,
+            });
+        }
+
+        Ok(Player {
+            movie,
+            frame: 0,
+            next_event: 0,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+}
+
+impl InputSource for Player {
+    fn poll(&mut self, emulator: &mut Emulator) {
+        while let Some(event) = self.movie.events.get(self.next_event) {
+            if event.frame > self.frame {
+                break;
+            }
+            emulator.set_btn_press(event.button, event.pressed);
+            self.next_event += 1;
+        }
+
+        self.frame += 1;
+    }
+}
+
+// A movie being recorded or played back by a frontend.
+pub enum MovieSession {
+    Record(Recorder),
+    Play(Player),
+}
+
+impl MovieSession {
+    // Whether a movie is being played and isn't over yet.
+    pub fn is_playing(&self) -> bool {
+        matches!(self, MovieSession::Play(player) if !player.is_finished())
+    }
+}
+
+impl InputSource for MovieSession {
+    fn poll(&mut self, emulator: &mut Emulator) {
+        match self {
+            MovieSession::Record(recorder) => recorder.poll(emulator),
+            MovieSession::Play(player) => player.poll(emulator),
+        }
+    }
+}
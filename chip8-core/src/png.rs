@@ -0,0 +1,90 @@
+// Minimal PNG encoder for 8-bit RGB images. Pixel data is stored without
+// compression, which keeps the encoder tiny; CHIP-8 screens are small anyway.
+
+use alloc::{vec, vec::Vec};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// Largest payload of a single stored deflate block.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// `pixels` holds `width * height` RGB triples, row by row.
+pub fn encode_rgb(width: u32, height: u32, pixels: &[(u8, u8, u8)]) -> Vec<u8> {
+    let mut png = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, truecolor, default compression and filter, no interlace.
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &header);
+
+    let mut raw = Vec::with_capacity(pixels.len() * 3 + height as usize);
+    for row in pixels.chunks(width as usize) {
+        // Every row starts with its filter type, 0 meaning none.
+        raw.push(0);
+        for (r, g, b) in row {
+            raw.extend_from_slice(&[*r, *g, *b]);
+        }
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, no preset dictionary.
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(MAX_STORED_BLOCK).collect();
+
+    for (i, block) in blocks.iter().enumerate() {
+        let is_last = i == blocks.len() - 1;
+        let len = block.len() as u16;
+
+        zlib.push(is_last as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,53 @@
+use core::time::Duration;
+
+// Real time is counted in 60ths of a nanosecond, so that a frame is exactly
+// 1/60 of a second long instead of a rounded number of nanoseconds.
+const FRAME: u128 = 1_000_000_000;
+
+// Frames run at most per update. After a longer stall (the window being dragged,
+// the machine going to sleep...) the emulator skips the missed time instead of
+// running a burst of frames to catch up.
+pub const MAX_FRAMES_PER_UPDATE: u32 = 4;
+
+// Fixed-timestep pacing for frontends driven by a real-time clock: frames, and
+// the timer ticks that end them, happen exactly 60 times per second however
+// often the display refreshes, and each frame runs its share of the
+// instructions per second.
+pub struct Scheduler {
+    instructions_per_second: u32,
+    // Real time not yet emulated, in 60ths of a nanosecond.
+    lag: u128,
+    // Instructions left over from frames that were owed a fraction of one, in 60ths.
+    instruction_remainder: u32,
+}
+
+impl Scheduler {
+    pub fn new(instructions_per_second: u32) -> Scheduler {
+        Scheduler {
+            instructions_per_second,
+            lag: 0,
+            instruction_remainder: 0,
+        }
+    }
+
+    // Adds the real time that passed since the last update, and returns how many
+    // frames are due. A display slower than 60Hz gets several frames per
+    // update, so only the last of them is shown; a faster one gets none on some
+    // updates.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.lag += elapsed.as_nanos() * 60;
+
+        let due = self.lag / FRAME;
+        self.lag %= FRAME;
+
+        due.min(MAX_FRAMES_PER_UPDATE as u128) as u32
+    }
+
+    // Instructions for the next frame: instructions per second / 60, with the
+    // remainder carried over so that every second runs exactly that many.
+    pub fn frame_instructions(&mut self) -> u32 {
+        let owed = self.instructions_per_second + self.instruction_remainder;
+        self.instruction_remainder = owed % 60;
+        owed / 60
+    }
+}
@@ -0,0 +1,161 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+};
+
+use crate::json::{self, Value};
+
+// Names for the addresses of a ROM, loaded from one of two formats:
+//
+// - the symbol file `asm` writes, one `0x0200 name` line per label;
+// - a JSON symbol map, for programs built with other tools (such as Octo)
+//   by converting their symbols to it, as files written by Octo itself
+//   aren't read:
+//
+//   {
+//     "source": "game.8o",
+//     "labels": { "main": 512, "draw-player": "0x23A" },
+//     "breakpoints": { "0x20A": "check-collision" },
+//     "lines": { "0x200": 12, "0x202": 13 }
+//   }
+//
+//   `breakpoints` are the `:breakpoint` directives of the source, and `lines`
+//   maps the address of each instruction to the source line it came from.
+//   Addresses are numbers, or strings in decimal or `0x` hex.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    source: Option<String>,
+    labels: BTreeMap<u16, String>,
+    addresses: BTreeMap<String, u16>,
+    breakpoints: BTreeMap<u16, String>,
+    lines: BTreeMap<u16, usize>,
+}
+
+impl Symbols {
+    pub fn parse(text: &str) -> Result<Symbols, String> {
+        if text.trim_start().starts_with('{') {
+            Symbols::parse_json(text)
+        } else {
+            Symbols::parse_text(text)
+        }
+    }
+
+    fn parse_text(text: &str) -> Result<Symbols, String> {
+        let mut symbols = Symbols::default();
+
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (address, name) = line
+                .split_once(char::is_whitespace)
+                .and_then(|(address, name)| Some((parse_address(address)?, name.trim())))
+                .ok_or(format!("Invalid symbol on line {}: {line}", i + 1))?;
+            symbols.add_label(name, address);
+        }
+
+        Ok(symbols)
+    }
+
+    fn parse_json(text: &str) -> Result<Symbols, String> {
+        let export = json::parse(text)?;
+        let mut symbols = Symbols {
+            source: export
+                .get("source")
+                .and_then(Value::as_str)
+                .map(String::from),
+            ..Symbols::default()
+        };
+
+        for (name, address) in members(&export, "labels") {
+            let address = json_address(address).ok_or(format!("Invalid address for {name}"))?;
+            symbols.add_label(name, address);
+        }
+
+        // Keyed by address or by name.
+        for (key, value) in members(&export, "breakpoints") {
+            let (address, name) = match (parse_address(key), value) {
+                (Some(address), Value::String(name)) => (address, name.clone()),
+                _ => {
+                    let address =
+                        json_address(value).ok_or(format!("Invalid breakpoint: {key}"))?;
+                    (address, key.clone())
+                }
+            };
+            symbols.breakpoints.insert(address, name);
+        }
+
+        for (address, line) in members(&export, "lines") {
+            let line = line.as_u64().ok_or(format!("Invalid line for {address}"))?;
+            let address = parse_address(address).ok_or(format!("Invalid address: {address}"))?;
+            symbols.lines.insert(address, line as usize);
+        }
+
+        Ok(symbols)
+    }
+
+    fn add_label(&mut self, name: &str, address: u16) {
+        // When several labels share an address, the first one names it.
+        self.labels
+            .entry(address)
+            .or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.breakpoints.is_empty() && self.lines.is_empty()
+    }
+
+    // The source file named by the export, if any.
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    // Labels by address, as `Instruction::render` takes them.
+    pub fn labels(&self) -> &BTreeMap<u16, String> {
+        &self.labels
+    }
+
+    pub fn address_of(&self, label: &str) -> Option<u16> {
+        self.addresses.get(label).copied()
+    }
+
+    // The `:breakpoint` directives, by address.
+    pub fn breakpoints(&self) -> &BTreeMap<u16, String> {
+        &self.breakpoints
+    }
+
+    // `label` or `label+offset`, from the closest label at or before `address`.
+    pub fn describe(&self, address: u16) -> Option<String> {
+        let (start, label) = self.labels.range(..=address).next_back()?;
+        Some(match address - start {
+            0 => label.clone(),
+            offset => format!("{label}+{offset}"),
+        })
+    }
+
+    // The source line of the instruction at `address`.
+    pub fn source_line(&self, address: u16) -> Option<usize> {
+        self.lines.get(&address).copied()
+    }
+}
+
+fn members<'a>(value: &'a Value, key: &str) -> impl Iterator<Item = &'a (String, Value)> {
+    let members = match value.get(key) {
+        Some(Value::Object(members)) => &members[..],
+        _ => &[],
+    };
+    members.iter()
+}
+
+fn parse_address(s: &str) -> Option<u16> {
+    json::parse_integer(s).and_then(|address| u16::try_from(address).ok())
+}
+
+fn json_address(value: &Value) -> Option<u16> {
+    value
+        .as_u64()
+        .and_then(|address| u16::try_from(address).ok())
+}
@@ -0,0 +1,57 @@
+use chip8_core::audio::NullAudio;
+use chip8_core::compare::{Step, compare};
+use chip8_core::emulator::Emulator;
+
+// LD V0, 10; LD DT, V0; then DRW V0, V0, 1 and JP back to it, forever.
+const ROM: [u8; 8] = [0x60, 0x0A, 0xF0, 0x15, 0xD0, 0x01, 0x12, 0x04];
+
+fn emulator(quirks: &str) -> Emulator {
+    let mut emulator = Emulator::new(quirks.parse().unwrap());
+    emulator.load_rom_bytes(&ROM).unwrap();
+    emulator
+}
+
+fn step(pc: u16, delay_timer: u8) -> Step {
+    Step {
+        pc: Some(pc),
+        delay_timer: Some(delay_timer),
+        ..Step::default()
+    }
+}
+
+// With `display_wait`, every sprite drawn ends the frame, so DT goes down once
+// per DRW rather than once per 20 instructions.
+#[test]
+fn timers_tick_at_the_end_of_display_wait_frames() {
+    let steps = [
+        step(0x200, 0),
+        step(0x202, 0),
+        step(0x204, 10),
+        step(0x206, 9),
+        step(0x204, 9),
+        step(0x206, 8),
+        step(0x204, 8),
+        step(0x206, 7),
+    ];
+
+    let divergence = compare(&mut emulator("vip"), &steps, 1200).unwrap();
+    assert_eq!(divergence, None);
+}
+
+// compare-trace must tick the timers where a run in frames, as in the window
+// and headless runs, does.
+#[test]
+fn timers_match_a_run_in_frames() {
+    let mut reference = emulator("vip");
+    for _ in 0..5 {
+        reference.run_frame(20, &mut NullAudio).unwrap();
+    }
+    // The two LDs and DRW, then a JP and DRW per frame.
+    let instructions = 3 + 2 * 4;
+    assert_eq!(reference.pc(), 0x206);
+
+    let mut steps = vec![Step::default(); instructions];
+    steps.push(step(reference.pc(), reference.timers().0));
+    let divergence = compare(&mut emulator("vip"), &steps, 1200).unwrap();
+    assert_eq!(divergence, None);
+}
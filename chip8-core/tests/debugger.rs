@@ -0,0 +1,66 @@
+use chip8_core::audio::NullAudio;
+use chip8_core::debugger::{Debugger, Stop};
+use chip8_core::emulator::Emulator;
+use chip8_core::emulator::quirks::Quirks;
+
+fn debugger_with(rom: &[u8], commands: &[&str]) -> (Debugger, Emulator) {
+    let mut emulator = Emulator::new(Quirks::default());
+    emulator.load_rom_bytes(rom).unwrap();
+
+    let mut debugger = Debugger::new();
+    for command in commands {
+        let command = debugger.parse_command(command).unwrap();
+        debugger.execute(command, &mut emulator).unwrap();
+    }
+
+    (debugger, emulator)
+}
+
+fn run_frame(debugger: &mut Debugger, emulator: &mut Emulator) {
+    debugger.run_frame(emulator, 20, &mut NullAudio).unwrap();
+}
+
+#[test]
+fn continue_runs_past_a_condition_that_still_holds() {
+    // LD I, 0xE01, then ADD V0, 1 forever.
+    let rom = [0xAE, 0x01, 0x70, 0x01, 0x12, 0x02];
+    let (mut debugger, mut emulator) = debugger_with(&rom, &["when I > 0xE00"]);
+
+    run_frame(&mut debugger, &mut emulator);
+    assert!(matches!(debugger.take_stop(), Some(Stop::Condition(_))));
+    assert_eq!(emulator.pc(), 0x202);
+
+    debugger.resume();
+    run_frame(&mut debugger, &mut emulator);
+    assert!(!debugger.is_paused());
+    assert_eq!(debugger.take_stop(), None);
+    assert!(emulator.v_registers()[0] > 1);
+}
+
+#[test]
+fn condition_stops_again_once_it_becomes_true_again() {
+    // LD I, 0xE01, ADD V0, 1, LD I, 0, then back to the start.
+    let rom = [0xAE, 0x01, 0x70, 0x01, 0xA0, 0x00, 0x12, 0x00];
+    let (mut debugger, mut emulator) = debugger_with(&rom, &["when I > 0xE00"]);
+
+    run_frame(&mut debugger, &mut emulator);
+    assert!(matches!(debugger.take_stop(), Some(Stop::Condition(_))));
+
+    debugger.resume();
+    run_frame(&mut debugger, &mut emulator);
+    assert!(matches!(debugger.take_stop(), Some(Stop::Condition(_))));
+    assert_eq!(emulator.pc(), 0x202);
+    assert_eq!(emulator.v_registers()[0], 1);
+}
+
+#[test]
+fn stepping_into_a_condition_does_not_stop_continue() {
+    let rom = [0xAE, 0x01, 0x70, 0x01, 0x12, 0x02];
+    let (mut debugger, mut emulator) = debugger_with(&rom, &["when I > 0xE00", "s"]);
+    assert_eq!(debugger.take_stop(), Some(Stop::Step));
+
+    debugger.resume();
+    run_frame(&mut debugger, &mut emulator);
+    assert!(!debugger.is_paused());
+    assert_eq!(debugger.take_stop(), None);
+}
@@ -0,0 +1,133 @@
+// Golden images of the test ROMs in `roms`: each one runs headless for a fixed
+// number of frames, and the hash of its final screen must match the one
+// recorded when the screen was checked by eye.
+use std::fs;
+
+use chip8_core::emulator::{Emulator, quirks::Quirks};
+use chip8_core::headless;
+use chip8_core::movie::{Movie, MovieEvent, Player};
+
+const INSTRUCTIONS_PER_SECOND: u32 = 1200;
+
+// Runs `rom` with the `quirks` preset, pressing each of `buttons` in turn: for
+// 5 frames, one second apart, starting after a second.
+fn screen_hash(rom: &str, quirks: &str, frames: u64, buttons: &[u8]) -> String {
+    let path = format!("{}/../roms/{rom}", env!("CARGO_MANIFEST_DIR"));
+    let rom = fs::read(&path).unwrap_or_else(|err| panic!("Could not read {path}: {err}"));
+
+    let quirks: Quirks = quirks.parse().unwrap();
+    let mut emulator = Emulator::new(quirks);
+    emulator.load_rom_bytes(&rom).unwrap();
+    emulator.seed_rng(0);
+
+    let events = buttons
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &button)| {
+            let frame = 60 * (i as u64 + 1);
+            [(frame, true), (frame + 5, false)].map(|(frame, pressed)| MovieEvent {
+                frame,
+                button,
+                pressed,
+            })
+        })
+        .collect();
+    let movie = Movie {
+        rom_hash: *emulator.rom_hash(),
+        quirks,
+        seed: 0,
+        instructions_per_second: INSTRUCTIONS_PER_SECOND,
+        frames,
+        events,
+    };
+
+    let mut player = Player::new(movie, &emulator).unwrap();
+    headless::run(&mut emulator, frames, INSTRUCTIONS_PER_SECOND, &mut player).unwrap();
+    headless::display_hash(&emulator)
+}
+
+#[test]
+fn logo() {
+    assert_eq!(
+        screen_hash("1-logo.ch8", "vip", 300, &[]),
+        "9cf813dd24e4c9e163744f13a988f1b9f83cdfdf561ca7655c187e3646306062"
+    );
+}
+
+#[test]
+fn ibm_logo() {
+    assert_eq!(
+        screen_hash("2-ibm-logo.ch8", "vip", 300, &[]),
+        "3b3afd39a4ac54b8e3fb134ed538ae1a4d4e49fc8e191dc7a88a444903f38b52"
+    );
+}
+
+#[test]
+fn corax_plus() {
+    assert_eq!(
+        screen_hash("3-corax+.ch8", "vip", 300, &[]),
+        "2d4286d495587d5ec824fe160e3402f4c75635411f04c50801687e3da0be00c3"
+    );
+}
+
+#[test]
+fn flags() {
+    assert_eq!(
+        screen_hash("4-flags.ch8", "vip", 300, &[]),
+        "a97dcd987c6b547702a591c81445224a057ba8d9cce250ec781f986b14265e50"
+    );
+}
+
+// The quirks test asks for a platform first: 1 for CHIP-8, 2 then 1 for
+// modern SUPER-CHIP, 2 then 2 for legacy SUPER-CHIP, 3 for XO-CHIP. Every
+// check passes on the matching preset.
+#[test]
+fn quirks_vip() {
+    assert_eq!(
+        screen_hash("5-quirks.ch8", "vip", 900, &[1]),
+        "1c38335bae83f3522f4560e0466bc21363647f3b5e02bd79938a75ae4acaec6f"
+    );
+}
+
+#[test]
+fn quirks_superchip() {
+    assert_eq!(
+        screen_hash("5-quirks.ch8", "schip", 900, &[2, 1]),
+        "e84abf718f2de7ca5470cc47d9c4ba40fba30e1bb0803124fb5c4f4c5c1ec12f"
+    );
+}
+
+// CHIP-48 has no entry of its own, and differs from legacy SUPER-CHIP in how
+// Fx55/Fx65 move I and in not waiting for the display, which the test reports.
+#[test]
+fn quirks_chip48() {
+    assert_eq!(
+        screen_hash("5-quirks.ch8", "chip48", 900, &[2, 2]),
+        "05794789d6e4ed414b799a3135e4889c446ef1753ddfd8060efe79479404bb87"
+    );
+}
+
+#[test]
+fn quirks_xochip() {
+    assert_eq!(
+        screen_hash("5-quirks.ch8", "xochip", 900, &[3]),
+        "a51472367130da6d27b66dac2d588eaceaf56de487d8b1abfd35d4d605bbcbe6"
+    );
+}
+
+// Picks the Fx0A test, which waits for a key, then presses and releases one.
+#[test]
+fn keypad() {
+    assert_eq!(
+        screen_hash("6-keypad.ch8", "vip", 300, &[3, 5]),
+        "fc7a065ca19e57b6f2f44d55fa9cb1e40623d205b555429b141be83da1ec9ec4"
+    );
+}
+
+#[test]
+fn beep() {
+    assert_eq!(
+        screen_hash("7-beep.ch8", "vip", 300, &[]),
+        "d0bc1ede43bb925bab81eeb7b78fc6ab76f4139e60674240d61a73b35c7f91e5"
+    );
+}
@@ -1,35 +1,106 @@
-use sdl2::audio::{ AudioCallback};
-pub trait AudioDeviceControl {
-    fn resume(&self);
-    fn pause(&self);
-}
-pub struct SquareWave {
-    pub phase_inc: f32,
-    pub phase: f32,
-    pub volume: f32,
+use sdl2::AudioSubsystem;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use chip8_core::audio::ring::{self, Consumer};
+use chip8_core::audio::{AudioSink, Beep, Synth};
+use chip8_core::emulator::consts::AUDIO_PATTERN_SIZE;
+
+use crate::config::Config;
+
+const SAMPLE_RATE: i32 = 44100;
+
+// Plays the samples the emulator made, as they come.
+pub struct Beeper {
+    samples: Consumer,
+    // Set when the samples ran out, until there are enough again to get
+    // through the next callbacks without running out again straight away.
+    buffering: bool,
+    // Samples to wait for when buffering: two callbacks' worth.
+    threshold: usize,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for Beeper {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
+        if self.buffering && self.samples.len() < self.threshold {
+            out.fill(0.0);
+            return;
+        }
+        self.buffering = false;
+
         for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            *x = self.samples.pop().unwrap_or_else(|| {
+                // The emulator is paused or fell behind.
+                self.buffering = true;
+                0.0
+            });
         }
     }
 }
 
-impl AudioDeviceControl for sdl2::audio::AudioDevice<SquareWave> {
-    fn resume(&self) {
-        self.resume();
+// The beep set in the `[beep]` section of the config, then by `--beep`.
+pub fn beep_from_config(config: &Config, overrides: Option<&str>) -> Result<Beep, String> {
+    let mut beep = Beep::default();
+
+    for section in config.sections("beep") {
+        for entry in &section.entries {
+            beep.set(&entry.name, &entry.value)
+                .map_err(|err| format!("{}:{}: {err}", config.path().display(), entry.line))?;
+        }
+    }
+    if let Some(overrides) = overrides {
+        beep.apply(overrides)?;
+    }
+
+    Ok(beep)
+}
+
+// The SDL playback device, fed by a synth the emulator drives.
+pub struct Speaker {
+    // Kept only to keep playing.
+    _device: AudioDevice<Beeper>,
+    synth: Synth,
+}
+
+impl Speaker {
+    pub fn open(audio: &AudioSubsystem, beep: Beep) -> Result<Speaker, String> {
+        let spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(1024),
+        };
+
+        // About 90ms of samples can wait to be played, so sound lags the
+        // screen by that much at most.
+        let (producer, consumer) = ring::ring(4096);
+        let device = audio.open_playback(None, &spec, |spec| Beeper {
+            samples: consumer,
+            buffering: true,
+            threshold: 2 * spec.samples as usize,
+        })?;
+        device.resume();
+
+        // The device may not have got the rate it asked for.
+        let synth = Synth::new(beep, device.spec().freq as u32, producer);
+        Ok(Speaker {
+            _device: device,
+            synth,
+        })
+    }
+}
+
+impl AudioSink for Speaker {
+    fn resume(&mut self) {
+        self.synth.resume();
+    }
+    fn pause(&mut self) {
+        self.synth.pause();
+    }
+    fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, pitch: u8) {
+        self.synth.set_pattern(pattern, pitch);
     }
-    fn pause(&self) {
-        self.pause();
+    fn tick(&mut self) {
+        self.synth.tick();
     }
 }
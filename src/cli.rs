@@ -0,0 +1,387 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use chip8_core::audio::Beep;
+use chip8_core::disasm::Syntax;
+use chip8_core::emulator::quirks::Quirks;
+
+use crate::key2btn::Layout;
+
+// Exit codes, one per class of failure.
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_ROM: i32 = 3;
+pub const EXIT_SDL: i32 = 4;
+pub const EXIT_FAULT: i32 = 5;
+pub const EXIT_MISMATCH: i32 = 6;
+pub const EXIT_ASM: i32 = 7;
+
+pub const USAGE: &str = "\
+Usage: chip8-emulator [run] <ROM> [OPTIONS]
+       chip8-emulator disasm <ROM> [--syntax <SYNTAX>]
+       chip8-emulator asm <SOURCE> [-o <ROM>]
+       chip8-emulator compare-trace <ROM> <TRACE> [OPTIONS]
+
+Commands:
+  run                  Run a ROM (the default)
+  disasm               Print the disassembly of a ROM
+  asm                  Assemble a source file written with classic mnemonics
+                       into a ROM, plus a symbol file next to it (.sym)
+  compare-trace        Run a ROM one instruction per step of a trace recorded by
+                       another emulator, stopping where the registers first
+                       differ (takes --quirks, --ips and --seed)
+
+Arguments:
+  <ROM>                Path to the ROM
+
+Options:
+  --quirks <PROFILE>   Platform preset (vip, chip48, schip, xochip), optionally
+                       followed by overrides, e.g. schip,clipping=off [default: vip]
+  --ips <N>            Instructions executed per second [default: 1200]
+  --scale <N>          Size in window pixels of a low-res pixel [default: 10]
+  --palette <COLORS>   Comma-separated hex colors for background, plane 1,
+                       plane 2 and both planes, e.g. 000000,ffffff
+  --mute               Disable sound
+  --rewind-seconds <N> How far back Backspace can rewind, 0 to turn rewinding
+                       off [default: 10]
+  --beep <SETTINGS>    Waveform (square, sine, triangle, noise), optionally
+                       followed by settings, e.g. sine,frequency=440,volume=0.1
+                       (see also the [beep] section of config.ini)
+  --headless           Run without a window, printing the final screen
+  --frames <N>         Stop after N frames (required with --headless, unless
+                       playing a movie)
+  --expect-hash <SHA>  With --headless, check the SHA-256 of the final screen
+  --png <PATH>         With --headless, save the final screen as a PNG image
+  --seed <N>           Seed for the random number generator
+  --record <PATH>      Record the button presses of the run into a movie file
+  --play <PATH>        Play back a movie, with the quirks, seed and --ips it was
+                       recorded with
+  --keymap <LAYOUT>    Keyboard layout: qwerty, azerty or dvorak [default: qwerty];
+                       keys can be changed further in config.ini
+  --debug              Start paused, reading debugger commands from standard input
+  --trace <PATH>       Write every instruction run, with the registers it changed, to PATH
+  --trace-range <A-B>  Only trace instructions at hex addresses A to B, e.g. 200-2ff
+  --trace-limit <SIZE> Stop tracing after SIZE bytes, e.g. 500K or 2G [default: 64M]
+  --symbols <PATH>     Symbol map for the debugger: a .sym file written by asm, or
+                       a JSON symbol map [default: ROM with a .sym extension, if any]
+  --syntax <SYNTAX>    With disasm, mnemonics to use: octo or classic [default: octo]
+  -o <ROM>             With asm, where to write the ROM [default: SOURCE with a .ch8 extension]
+  -h, --help           Print this help
+
+Exit codes:
+  0  success
+  2  invalid command line
+  3  ROM, symbol map, trace file or movie could not be opened
+  4  window or audio could not be initialized
+  5  the emulator stopped on an error
+  6  the final screen did not match --expect-hash, or the trace did not match
+  7  the source could not be assembled
+";
+
+pub enum Command {
+    Run(Box<Options>),
+    Disasm {
+        rom_path: String,
+        syntax: Syntax,
+    },
+    Asm {
+        source_path: String,
+        rom_path: String,
+    },
+    CompareTrace {
+        rom_path: String,
+        trace_path: String,
+        quirks: Quirks,
+        instructions_per_second: u32,
+        seed: Option<u64>,
+    },
+    Help,
+}
+
+// Window-only settings go unused in builds without the `sdl` feature.
+#[cfg_attr(not(feature = "sdl"), allow(dead_code))]
+pub struct Options {
+    pub rom_path: String,
+    pub quirks: Quirks,
+    pub instructions_per_second: u32,
+    pub scale: u32,
+    pub palette: [(u8, u8, u8); 4],
+    pub mute: bool,
+    pub rewind_seconds: usize,
+    pub beep: Option<String>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub expect_hash: Option<String>,
+    pub png_path: Option<String>,
+    pub seed: Option<u64>,
+    pub layout: Layout,
+    pub debug: bool,
+    pub symbols_path: Option<String>,
+    pub trace_path: Option<String>,
+    pub trace_range: RangeInclusive<u16>,
+    pub trace_limit: usize,
+    pub record_path: Option<String>,
+    pub play_path: Option<String>,
+}
+
+pub const DEFAULT_PALETTE: [(u8, u8, u8); 4] =
+    [(0, 0, 0), (255, 255, 255), (170, 170, 170), (85, 85, 85)];
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+
+    // `run` is the default subcommand and may be left out.
+    match args.peek().map(String::as_str) {
+        Some("run") => {
+            args.next();
+        }
+        Some("disasm") => {
+            args.next();
+            return parse_disasm(args);
+        }
+        Some("asm") => {
+            args.next();
+            return parse_asm(args);
+        }
+        Some("compare-trace") => {
+            args.next();
+            return parse_compare_trace(args);
+        }
+        _ => {}
+    }
+
+    let mut rom_path = None;
+    let mut options = Options {
+        rom_path: String::new(),
+        quirks: Quirks::default(),
+        instructions_per_second: 1200,
+        scale: 10,
+        palette: DEFAULT_PALETTE,
+        mute: false,
+        rewind_seconds: 10,
+        beep: None,
+        headless: false,
+        frames: None,
+        expect_hash: None,
+        png_path: None,
+        seed: None,
+        layout: Layout::Qwerty,
+        debug: false,
+        symbols_path: None,
+        trace_path: None,
+        trace_range: 0..=0xFFFF,
+        trace_limit: 64 << 20,
+        record_path: None,
+        play_path: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--mute" => options.mute = true,
+            "--beep" => {
+                let settings = value(&mut args, &arg)?;
+                // Checked here, but applied on top of config.ini once it is read.
+                Beep::default().apply(&settings)?;
+                options.beep = Some(settings);
+            }
+            "--headless" => options.headless = true,
+            "--debug" => options.debug = true,
+            "--quirks" => options.quirks = value(&mut args, &arg)?.parse()?,
+            "--ips" => options.instructions_per_second = number(&mut args, &arg)?,
+            "--scale" => options.scale = number(&mut args, &arg)?,
+            "--rewind-seconds" => options.rewind_seconds = number(&mut args, &arg)?,
+            "--palette" => options.palette = parse_palette(&value(&mut args, &arg)?)?,
+            "--frames" => options.frames = Some(number(&mut args, &arg)?),
+            "--expect-hash" => {
+                options.expect_hash = Some(value(&mut args, &arg)?.to_ascii_lowercase())
+            }
+            "--png" => options.png_path = Some(value(&mut args, &arg)?),
+            "--seed" => options.seed = Some(number(&mut args, &arg)?),
+            "--keymap" => options.layout = value(&mut args, &arg)?.parse()?,
+            "--symbols" => options.symbols_path = Some(value(&mut args, &arg)?),
+            "--record" => options.record_path = Some(value(&mut args, &arg)?),
+            "--play" => options.play_path = Some(value(&mut args, &arg)?),
+            "--trace" => options.trace_path = Some(value(&mut args, &arg)?),
+            "--trace-range" => options.trace_range = parse_range(&value(&mut args, &arg)?)?,
+            "--trace-limit" => options.trace_limit = parse_size(&value(&mut args, &arg)?)?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+
+    options.rom_path = rom_path.ok_or("Missing ROM path")?;
+
+    if options.instructions_per_second == 0 || options.scale == 0 {
+        return Err("--ips and --scale must be greater than 0".into());
+    }
+    if options.headless && options.frames.is_none() && options.play_path.is_none() {
+        return Err("--headless requires --frames or --play".into());
+    }
+    if options.record_path.is_some() && options.play_path.is_some() {
+        return Err("--record and --play can't be used together".into());
+    }
+    if options.headless && (options.debug || options.symbols_path.is_some()) {
+        return Err("--debug and --symbols can't be used with --headless".into());
+    }
+    if !options.headless && (options.expect_hash.is_some() || options.png_path.is_some()) {
+        return Err("--expect-hash and --png require --headless".into());
+    }
+
+    Ok(Command::Run(Box::new(options)))
+}
+
+fn parse_disasm(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    let mut rom_path = None;
+    let mut syntax = Syntax::Octo;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--syntax" => syntax = value(&mut args, &arg)?.parse()?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+
+    Ok(Command::Disasm {
+        rom_path: rom_path.ok_or("Missing ROM path")?,
+        syntax,
+    })
+}
+
+fn parse_asm(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    let mut source_path = None;
+    let mut rom_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" => rom_path = Some(value(&mut args, &arg)?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+            _ if source_path.is_none() => source_path = Some(arg),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+
+    let source_path: String = source_path.ok_or("Missing source path")?;
+    let rom_path = rom_path.unwrap_or_else(|| {
+        Path::new(&source_path)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    Ok(Command::Asm {
+        source_path,
+        rom_path,
+    })
+}
+
+fn parse_compare_trace(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    let mut paths = Vec::new();
+    let mut quirks = Quirks::default();
+    let mut instructions_per_second = 1200;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--quirks" => quirks = value(&mut args, &arg)?.parse()?,
+            "--ips" => instructions_per_second = number(&mut args, &arg)?,
+            "--seed" => seed = Some(number(&mut args, &arg)?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {arg}")),
+            _ if paths.len() < 2 => paths.push(arg),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    let rom_path = paths.next().ok_or("Missing ROM path")?;
+    let trace_path = paths.next().ok_or("Missing trace path")?;
+    if instructions_per_second == 0 {
+        return Err("--ips must be greater than 0".into());
+    }
+
+    Ok(Command::CompareTrace {
+        rom_path,
+        trace_path,
+        quirks,
+        instructions_per_second,
+        seed,
+    })
+}
+
+fn value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next().ok_or(format!("Missing value for {option}"))
+}
+
+fn number<T: std::str::FromStr>(
+    args: &mut impl Iterator<Item = String>,
+    option: &str,
+) -> Result<T, String> {
+    let value = value(args, option)?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {option}: {value}"))
+}
+
+// Hex addresses such as `200-2ff` or `0x200-0x2FF`.
+fn parse_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let address = |s: &str| {
+        let hex = s.trim().trim_start_matches("0x");
+        u16::from_str_radix(hex, 16).map_err(|_| format!("Invalid address: {s}"))
+    };
+
+    let (start, end) = value
+        .split_once('-')
+        .ok_or(format!("Invalid address range: {value}"))?;
+    let (start, end) = (address(start)?, address(end)?);
+    if end < start {
+        return Err(format!("Empty address range: {value}"));
+    }
+
+    Ok(start..=end)
+}
+
+// Bytes, optionally followed by K, M or G.
+fn parse_size(value: &str) -> Result<usize, String> {
+    let (number, shift) = match value.to_ascii_uppercase().chars().last() {
+        Some('K') => (&value[..value.len() - 1], 10),
+        Some('M') => (&value[..value.len() - 1], 20),
+        Some('G') => (&value[..value.len() - 1], 30),
+        _ => (value, 0),
+    };
+
+    number
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .ok_or(format!("Invalid size: {value}"))
+}
+
+// Unlisted colors keep their default, so "000000,ffffff" only changes the first two.
+fn parse_palette(value: &str) -> Result<[(u8, u8, u8); 4], String> {
+    let mut palette = DEFAULT_PALETTE;
+    let colors: Vec<&str> = value.split(',').map(str::trim).collect();
+
+    if colors.len() > palette.len() {
+        return Err(format!("Too many colors in palette: {value}"));
+    }
+
+    for (entry, color) in palette.iter_mut().zip(colors) {
+        let hex = color.trim_start_matches('#');
+        let rgb = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid color: {color}"))?;
+        if hex.len() != 6 {
+            return Err(format!("Invalid color: {color}"));
+        }
+
+        *entry = ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    }
+
+    Ok(palette)
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// Settings read from `config.ini` in the user's config directory, e.g.
+// `~/.config/chip8-emulator/config.ini`:
+//
+//   # Comments start with # or ;
+//   [keys]
+//   5 = W, Up
+//
+//   [rom:pong.ch8]
+//   1 = sc:Q
+//
+// Sections hold `name = value` lines. Values may be quoted, as in TOML.
+#[derive(Default)]
+pub struct Config {
+    path: PathBuf,
+    sections: Vec<Section>,
+}
+
+pub struct Section {
+    pub name: String,
+    pub entries: Vec<Entry>,
+}
+
+pub struct Entry {
+    pub line: usize,
+    pub name: String,
+    pub value: String,
+}
+
+impl Config {
+    // A missing file is an empty config.
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = config_path() else {
+            return Ok(Config::default());
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text, path),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(format!("Could not read {}: {err}", path.display())),
+        }
+    }
+
+    fn parse(text: &str, path: PathBuf) -> Result<Config, String> {
+        let mut sections: Vec<Section> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                sections.push(Section {
+                    name: name.trim().to_string(),
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+
+            let (Some((name, value)), Some(section)) = (line.split_once('='), sections.last_mut())
+            else {
+                return Err(format!(
+                    "{}:{}: expected [section] or name = value",
+                    path.display(),
+                    i + 1
+                ));
+            };
+            section.entries.push(Entry {
+                line: i + 1,
+                name: name.trim().to_string(),
+                value: value.trim().trim_matches('"').to_string(),
+            });
+        }
+
+        Ok(Config { path, sections })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Every section with that name, in file order.
+    pub fn sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Section> {
+        self.sections
+            .iter()
+            .filter(move |section| section.name == name)
+    }
+}
+
+// `$XDG_CONFIG_HOME/chip8-emulator/config.ini`, defaulting to `~/.config`
+// (or `%APPDATA%` on Windows) when XDG_CONFIG_HOME isn't set.
+fn config_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .map(|dir| dir.join("chip8-emulator").join("config.ini"))
+}
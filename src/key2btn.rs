@@ -1,27 +1,220 @@
-use sdl2::keyboard::Keycode;
+#[cfg(feature = "sdl")]
+use std::collections::HashMap;
+use std::str::FromStr;
 
-pub fn key2btn(key: Keycode) -> Option<u8> {
+#[cfg(feature = "sdl")]
+use sdl2::keyboard::{Keycode, Scancode};
+
+#[cfg(feature = "sdl")]
+use crate::config::{Config, Entry};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            _ => Err(format!("Unknown keyboard layout: {s}")),
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+// Every layout maps the same physical 4x4 block of keys, the one under 1-4 on a QWERTY keyboard.
+pub fn key2btn(key: Keycode, layout: Layout) -> Option<u8> {
+    match layout {
+        Layout::Qwerty => qwerty(key),
+        Layout::Azerty => azerty(key),
+        Layout::Dvorak => dvorak(key),
+    }
+}
+
+#[cfg(feature = "sdl")]
+// The keys pressing each button: those of a layout, except for the buttons given
+// other keys in the config. The [keys] section applies to every ROM, and
+// [rom:NAME] sections to the ROM with that file name only, on top of [keys]:
+//
+//   [keys]
+//   5 = W, Up
+//   8 = S, Down
+//
+//   [rom:pong.ch8]
+//   1 = sc:Q
+//
+// Keys are SDL key names, or scancode names after `sc:` for a key at the same
+// place whatever the keyboard layout.
+pub struct Keymap {
+    layout: Layout,
+    // Buttons whose keys come from the config rather than from the layout.
+    configured: [bool; 16],
+    keycodes: HashMap<Keycode, u8>,
+    scancodes: HashMap<Scancode, u8>,
+}
+
+#[cfg(feature = "sdl")]
+impl Keymap {
+    // Just the keys of the layout.
+    pub fn new(layout: Layout) -> Keymap {
+        Keymap {
+            layout,
+            configured: [false; 16],
+            keycodes: HashMap::new(),
+            scancodes: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(layout: Layout, config: &Config, rom_name: &str) -> Result<Keymap, String> {
+        let mut keymap = Keymap::new(layout);
+
+        let rom_section = format!("rom:{rom_name}");
+        for section in config.sections("keys").chain(config.sections(&rom_section)) {
+            for entry in &section.entries {
+                keymap
+                    .configure(entry)
+                    .map_err(|err| format!("{}:{}: {err}", config.path().display(), entry.line))?;
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    // Gives a button the keys of a config entry, replacing the ones it had.
+    fn configure(&mut self, entry: &Entry) -> Result<(), String> {
+        let button = u8::from_str_radix(&entry.name, 16)
+            .ok()
+            .filter(|button| *button < 16)
+            .ok_or(format!("Unknown button: {}", entry.name))?;
+
+        self.configured[button as usize] = true;
+        self.keycodes.retain(|_, mapped| *mapped != button);
+        self.scancodes.retain(|_, mapped| *mapped != button);
+
+        for key in entry
+            .value
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+        {
+            match key.strip_prefix("sc:") {
+                Some(name) => {
+                    let scancode =
+                        Scancode::from_name(name).ok_or(format!("Unknown scancode: {name}"))?;
+                    self.scancodes.insert(scancode, button);
+                }
+                None => {
+                    let keycode = Keycode::from_name(key).ok_or(format!("Unknown key: {key}"))?;
+                    self.keycodes.insert(keycode, button);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn button(&self, keycode: Option<Keycode>, scancode: Option<Scancode>) -> Option<u8> {
+        let configured = scancode
+            .and_then(|scancode| self.scancodes.get(&scancode))
+            .or_else(|| keycode.and_then(|keycode| self.keycodes.get(&keycode)));
+        if let Some(button) = configured {
+            return Some(*button);
+        }
+
+        keycode
+            .and_then(|keycode| key2btn(keycode, self.layout))
+            .filter(|button| !self.configured[*button as usize])
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn qwerty(key: Keycode) -> Option<u8> {
     match key {
         Keycode::Num1 => Some(0x1),
         Keycode::Num2 => Some(0x2),
         Keycode::Num3 => Some(0x3),
         Keycode::Num4 => Some(0xC),
-        
+
         Keycode::Q => Some(0x4),
         Keycode::W => Some(0x5),
         Keycode::E => Some(0x6),
         Keycode::R => Some(0xD),
-        
+
         Keycode::A => Some(0x7),
         Keycode::S => Some(0x8),
         Keycode::D => Some(0x9),
         Keycode::F => Some(0xE),
-        
+
         Keycode::Z => Some(0xA),
         Keycode::X => Some(0x0),
         Keycode::C => Some(0xB),
         Keycode::V => Some(0xF),
-        
-        _ => None
+
+        _ => None,
+    }
+}
+
+#[cfg(feature = "sdl")]
+// SDL reports the unshifted symbol of AZERTY's number row, and 'é' has no named keycode.
+const KEYCODE_E_ACUTE: i32 = 'é' as i32;
+
+#[cfg(feature = "sdl")]
+fn azerty(key: Keycode) -> Option<u8> {
+    match key {
+        Keycode::Ampersand => Some(0x1),
+        _ if key.into_i32() == KEYCODE_E_ACUTE => Some(0x2),
+        Keycode::Quotedbl => Some(0x3),
+        Keycode::Quote => Some(0xC),
+
+        Keycode::A => Some(0x4),
+        Keycode::Z => Some(0x5),
+        Keycode::E => Some(0x6),
+        Keycode::R => Some(0xD),
+
+        Keycode::Q => Some(0x7),
+        Keycode::S => Some(0x8),
+        Keycode::D => Some(0x9),
+        Keycode::F => Some(0xE),
+
+        Keycode::W => Some(0xA),
+        Keycode::X => Some(0x0),
+        Keycode::C => Some(0xB),
+        Keycode::V => Some(0xF),
+
+        _ => None,
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn dvorak(key: Keycode) -> Option<u8> {
+    match key {
+        Keycode::Num1 => Some(0x1),
+        Keycode::Num2 => Some(0x2),
+        Keycode::Num3 => Some(0x3),
+        Keycode::Num4 => Some(0xC),
+
+        Keycode::Quote => Some(0x4),
+        Keycode::Comma => Some(0x5),
+        Keycode::Period => Some(0x6),
+        Keycode::P => Some(0xD),
+
+        Keycode::A => Some(0x7),
+        Keycode::O => Some(0x8),
+        Keycode::E => Some(0x9),
+        Keycode::U => Some(0xE),
+
+        Keycode::Semicolon => Some(0xA),
+        Keycode::Q => Some(0x0),
+        Keycode::J => Some(0xB),
+        Keycode::K => Some(0xF),
+
+        _ => None,
     }
-}
\ No newline at end of file
+}
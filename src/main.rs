@@ -1,140 +1,326 @@
+#[cfg(feature = "sdl")]
 mod audio;
-mod emulator;
+mod cli;
+#[cfg(feature = "sdl")]
+mod config;
 mod key2btn;
-use crate::audio::SquareWave;
-use crate::emulator::Emulator;
-use crate::emulator::consts::{SCREEN_HEIGHT, SCREEN_WIDTH};
-
-use std::process::exit;
-
-use sdl2::audio::{AudioSpecDesired};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+#[cfg(feature = "sdl")]
+mod window;
+use crate::cli::{Command, Options};
 
+use chip8_core::asm;
+use chip8_core::compare;
+use chip8_core::debugger::Debugger;
+use chip8_core::disasm::{self, Syntax};
+use chip8_core::emulator::Emulator;
+use chip8_core::emulator::quirks::Quirks;
+use chip8_core::emulator::trace::Tracer;
+use chip8_core::frontend::NullInput;
+use chip8_core::headless;
+use chip8_core::movie::{Movie, MovieSession, Player, Recorder};
+use chip8_core::symbols::Symbols;
 
+use std::env;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::process::exit;
 
 fn main() {
-    println!("Insert a ROM's filename (e.g., pong.ch8): ");
-    let mut filename: String = String::from("./roms/");
+    let mut options = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Run(options)) => *options,
+        Ok(Command::Disasm { rom_path, syntax }) => exit(run_disasm(&rom_path, syntax)),
+        Ok(Command::Asm {
+            source_path,
+            rom_path,
+        }) => exit(run_asm(&source_path, &rom_path)),
+        Ok(Command::CompareTrace {
+            rom_path,
+            trace_path,
+            quirks,
+            instructions_per_second,
+            seed,
+        }) => exit(run_compare_trace(
+            &rom_path,
+            &trace_path,
+            quirks,
+            instructions_per_second,
+            seed,
+        )),
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{}", cli::USAGE);
+            exit(cli::EXIT_USAGE);
+        }
+    };
+
+    let movie = options.play_path.as_ref().map(|path| {
+        match fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Movie::from_bytes(&data).map_err(|err| err.to_string()))
+        {
+            Ok(movie) => movie,
+            Err(err) => {
+                eprintln!("Could not load {path}: {err}");
+                exit(cli::EXIT_ROM);
+            }
+        }
+    });
 
-    if let Err(err) = std::io::stdin().read_line(&mut filename) {
-        print!("{err}");
-        exit(1)
+    if let Some(movie) = &movie {
+        // A movie only plays back the same with the settings it was recorded with.
+        options.quirks = movie.quirks;
+        options.seed = Some(movie.seed);
+        options.instructions_per_second = movie.instructions_per_second;
+        if options.headless {
+            options.frames = options.frames.or(Some(movie.frames));
+        }
     }
 
-    println!("Loading ROM: {filename}");
+    let mut emulator = Emulator::new(options.quirks);
+    if let Err(err) = emulator.load_rom(&options.rom_path) {
+        eprintln!("Could not load {}: {err}", options.rom_path);
+        exit(cli::EXIT_ROM);
+    }
 
-    let mut emulator = Emulator::new();
-    if let Err(err) = emulator.load_rom(filename.trim()) {
-        println!("{err}");
-        exit(2);
+    if let Some(seed) = options.seed {
+        emulator.seed_rng(seed);
     }
 
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let audio_subsystem = sdl_context.audio().unwrap();
-
-    let window = video_subsystem
-        .window(
-            "Chip-8 Emulator",
-            SCREEN_WIDTH as u32 * 10,
-            SCREEN_HEIGHT as u32 * 10,
-        )
-        .position_centered()
-        .build()
-        .unwrap();
-
-    let mut canvas: Canvas<sdl2::video::Window> =
-        window.into_canvas().present_vsync().build().unwrap();
-
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
-
-    let audio_spec = AudioSpecDesired {
-        freq: Some(44000),
-        channels: Some(2),
-        samples: Some(1024),
+    let mut session = match movie {
+        Some(movie) => match Player::new(movie, &emulator) {
+            Ok(player) => Some(MovieSession::Play(player)),
+            Err(err) => {
+                eprintln!("Could not play {}: {err}", options.rom_path);
+                exit(cli::EXIT_ROM);
+            }
+        },
+        None => options.record_path.as_ref().map(|_| {
+            MovieSession::Record(Recorder::new(&emulator, options.instructions_per_second))
+        }),
     };
 
-    let audio_device  = audio_subsystem
-        .open_playback(None, &audio_spec, |spec| {
-            // initialize the audio callback
-            SquareWave {
-                phase_inc: 150.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.05,
+    if let Some(path) = &options.trace_path {
+        match File::create(path) {
+            Ok(file) => emulator.set_tracer(Some(Tracer::new(
+                Box::new(BufWriter::new(file)),
+                options.trace_range.clone(),
+                options.trace_limit,
+            ))),
+            Err(err) => {
+                eprintln!("Could not create {path}: {err}");
+                exit(cli::EXIT_ROM);
             }
-        })
-        .unwrap();
-
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    const INSTRUCTIONS_PER_FRAME: usize = 20;
-
-    'running: loop {
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    break 'running;
-                }
-
-                Event::KeyDown {
-                    keycode: Some(key), ..
-                } => {
-                    if let Some(btn) = key2btn::key2btn(key) {
-                        emulator.set_btn_press(btn, true);
-                    }
-                }
-
-                Event::KeyUp {
-                    keycode: Some(key), ..
-                } => {
-                    if let Some(btn) = key2btn::key2btn(key) {
-                        emulator.set_btn_press(btn, false);
-                    }
-                }
-                _ => {}
+        }
+    }
+
+    let exit_code = if options.headless {
+        run_headless(&mut emulator, &options, session.as_mut())
+    } else {
+        match load_symbols(&options) {
+            Ok(symbols) => run_window(&mut emulator, &options, symbols, session.as_mut()),
+            Err(err) => {
+                eprintln!("{err}");
+                cli::EXIT_ROM
             }
         }
+    };
 
-        // Fetch, Decode, Execute Cycle
+    if let (Some(path), Some(MovieSession::Record(recorder))) = (&options.record_path, session) {
+        let movie = recorder.finish();
+        match fs::write(path, movie.to_bytes()) {
+            Ok(()) => println!("Recorded {} frames into {path}", movie.frames),
+            Err(err) => eprintln!("Could not save {path}: {err}"),
+        }
+    }
 
-        for _ in 0..INSTRUCTIONS_PER_FRAME {
-            emulator.execution_cycle();
+    // Flushes the trace file, which `exit` would otherwise leave unwritten.
+    emulator.set_tracer(None);
+    exit(exit_code);
+}
 
-            if emulator.draw_flag {
-                break;
-            }
+fn run_disasm(rom_path: &str, syntax: Syntax) -> i32 {
+    match fs::read(rom_path) {
+        Ok(rom) => {
+            print!("{}", disasm::disassemble(&rom, syntax));
+            0
+        }
+        Err(err) => {
+            eprintln!("Could not load {rom_path}: {err}");
+            cli::EXIT_ROM
+        }
+    }
+}
+
+fn run_asm(source_path: &str, rom_path: &str) -> i32 {
+    let source = match fs::read_to_string(source_path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Could not read {source_path}: {err}");
+            return cli::EXIT_ASM;
         }
+    };
+
+    // Include paths come joined to the directory of the file including them.
+    let mut read_file = |path: &str| fs::read_to_string(path).map_err(|err| err.to_string());
 
-        emulator.tick_timers(&audio_device);
+    let assembly = match asm::assemble(&source, source_path, &mut read_file) {
+        Ok(assembly) => assembly,
+        Err(err) => {
+            eprintln!("{err}");
+            return cli::EXIT_ASM;
+        }
+    };
 
-        draw_on_canvas(&mut canvas, &emulator.display);
-        canvas.present();
-        emulator.draw_flag = false;
+    let symbols_path = Path::new(rom_path).with_extension("sym");
+    let written = fs::write(rom_path, &assembly.rom)
+        .and_then(|()| fs::write(&symbols_path, assembly.symbol_file()));
+    if let Err(err) = written {
+        eprintln!("Could not save {rom_path}: {err}");
+        return cli::EXIT_ASM;
     }
+
+    println!(
+        "Assembled {} bytes into {rom_path} (symbols in {})",
+        assembly.rom.len(),
+        symbols_path.display()
+    );
+    0
 }
 
-fn draw_on_canvas(
-    canvas: &mut Canvas<sdl2::video::Window>,
-    display: &[[bool; SCREEN_WIDTH]; SCREEN_HEIGHT],
-) {
-    for (y, line) in display.iter().enumerate() {
-        for (x, pixel) in line.iter().enumerate() {
-            if *pixel {
-                canvas.set_draw_color(Color::RGB(255, 255, 255));
-            } else {
-                canvas.set_draw_color(Color::RGB(0, 0, 0));
+fn run_compare_trace(
+    rom_path: &str,
+    trace_path: &str,
+    quirks: Quirks,
+    instructions_per_second: u32,
+    seed: Option<u64>,
+) -> i32 {
+    let mut emulator = Emulator::new(quirks);
+    if let Err(err) = emulator.load_rom(rom_path) {
+        eprintln!("Could not load {rom_path}: {err}");
+        return cli::EXIT_ROM;
+    }
+    if let Some(seed) = seed {
+        emulator.seed_rng(seed);
+    }
+
+    let steps = match fs::read_to_string(trace_path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| compare::parse_trace(&text))
+    {
+        Ok(steps) => steps,
+        Err(err) => {
+            eprintln!("Could not load {trace_path}: {err}");
+            return cli::EXIT_ROM;
+        }
+    };
+
+    match compare::compare(&mut emulator, &steps, instructions_per_second) {
+        Ok(None) => {
+            println!("All {} steps match", steps.len());
+            0
+        }
+        Ok(Some(divergence)) => {
+            print!("{divergence}\n{}", Debugger::new().registers(&emulator));
+            cli::EXIT_MISMATCH
+        }
+        Err(err) => {
+            eprint!(
+                "Emulation halted: {err}\n{}",
+                Debugger::new().registers(&emulator)
+            );
+            cli::EXIT_FAULT
+        }
+    }
+}
+
+// The map given with --symbols, or the one `asm` wrote next to the ROM. Running
+// without one is fine, so a missing default file is not an error.
+fn load_symbols(options: &Options) -> Result<Symbols, String> {
+    let path = match &options.symbols_path {
+        Some(path) => path.clone(),
+        None => {
+            let path = Path::new(&options.rom_path).with_extension("sym");
+            if !path.exists() {
+                return Ok(Symbols::default());
             }
+            path.to_string_lossy().into_owned()
+        }
+    };
+
+    fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| Symbols::parse(&text))
+        .map_err(|err| format!("Could not load symbols from {path}: {err}"))
+}
 
-            let _ = canvas.fill_rect(Rect::new(x as i32 * 10, y as i32 * 10, 10, 10));
+fn run_headless(
+    emulator: &mut Emulator,
+    options: &Options,
+    movie: Option<&mut MovieSession>,
+) -> i32 {
+    let frames = options.frames.unwrap_or_default();
+    let instructions_per_second = options.instructions_per_second;
+    let result = match movie {
+        Some(movie) => headless::run(emulator, frames, instructions_per_second, movie),
+        None => headless::run(emulator, frames, instructions_per_second, &mut NullInput),
+    };
+
+    print!("{}", headless::display_to_text(emulator));
+
+    if let Err(err) = result {
+        eprintln!("Emulation halted: {err}");
+        return cli::EXIT_FAULT;
+    }
+
+    if let Some(path) = &options.png_path {
+        let png = headless::display_to_png(emulator, &options.palette);
+        if let Err(err) = fs::write(path, png) {
+            eprintln!("Could not save {path}: {err}");
+            return cli::EXIT_FAULT;
+        }
+    }
+
+    let hash = headless::display_hash(emulator);
+    println!("Screen hash: {hash}");
+
+    match &options.expect_hash {
+        Some(expected) if *expected != hash => {
+            eprintln!("Screen hash mismatch: expected {expected}");
+            cli::EXIT_MISMATCH
         }
+        _ => 0,
     }
 }
+
+#[cfg(feature = "sdl")]
+fn run_window(
+    emulator: &mut Emulator,
+    options: &Options,
+    symbols: Symbols,
+    movie: Option<&mut MovieSession>,
+) -> i32 {
+    println!("Loading ROM: {}", options.rom_path);
+
+    match window::run(emulator, options, symbols, movie) {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("Could not initialize SDL: {err}");
+            cli::EXIT_SDL
+        }
+    }
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_window(
+    _emulator: &mut Emulator,
+    _options: &Options,
+    _symbols: Symbols,
+    _movie: Option<&mut MovieSession>,
+) -> i32 {
+    eprintln!("This build has no window support (the `sdl` feature is off); use --headless");
+    cli::EXIT_SDL
+}
@@ -0,0 +1,530 @@
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Instant;
+use std::{fs, io, thread};
+
+use chip8_core::audio::{AudioSink, NullAudio};
+use chip8_core::debugger::{self, Debugger};
+use chip8_core::emulator::Emulator;
+use chip8_core::emulator::consts::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8_core::emulator::error::{EmulatorError, StepOutcome};
+use chip8_core::emulator::rewind::Rewind;
+use chip8_core::frontend::{DisplaySink, InputSource};
+use chip8_core::movie::MovieSession;
+use chip8_core::scheduler::Scheduler;
+use chip8_core::symbols::Symbols;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+
+use crate::audio::{Speaker, beep_from_config};
+use crate::cli::{self, Options};
+use crate::config::Config;
+use crate::key2btn::Keymap;
+
+pub fn run(
+    emulator: &mut Emulator,
+    options: &Options,
+    symbols: Symbols,
+    mut movie: Option<&mut MovieSession>,
+) -> Result<i32, String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let window = video_subsystem
+        .window(
+            "Chip-8 Emulator",
+            SCREEN_WIDTH as u32 * options.scale,
+            SCREEN_HEIGHT as u32 * options.scale,
+        )
+        .position_centered()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    let mut canvas: Canvas<sdl2::video::Window> = window
+        .into_canvas()
+        .present_vsync()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.present();
+
+    let mut screen = Screen {
+        canvas,
+        palette: options.palette.map(|(r, g, b)| Color::RGB(r, g, b)),
+        scale: options.scale,
+        halted: false,
+        paused: false,
+        show_registers: false,
+    };
+
+    // A broken config falls back to the defaults rather than keeping the ROM from running.
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("Ignoring the config file: {err}");
+        Config::default()
+    });
+    let rom_name = Path::new(&options.rom_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let keymap = Keymap::from_config(options.layout, &config, &rom_name).unwrap_or_else(|err| {
+        eprintln!("Using the default keys: {err}");
+        Keymap::new(options.layout)
+    });
+
+    let mut audio_device: Box<dyn AudioSink> = if options.mute {
+        Box::new(NullAudio)
+    } else {
+        let beep = beep_from_config(&config, options.beep.as_deref()).unwrap_or_else(|err| {
+            eprintln!("Using the default beep: {err}");
+            Default::default()
+        });
+        Box::new(Speaker::open(&sdl_context.audio()?, beep)?)
+    };
+
+    let mut event_pump = sdl_context.event_pump()?;
+    let mut frame_count: u64 = 0;
+    let mut state_slot: u8 = 0;
+    let mut rewind = Rewind::new(options.rewind_seconds.saturating_mul(FRAMES_PER_SECOND));
+    let mut rewinding = false;
+    let mut scheduler = Scheduler::new(options.instructions_per_second);
+    let mut last_update = Instant::now();
+
+    let mut debugger = Debugger::new();
+    debugger.set_symbols(symbols);
+    let console = options.debug.then(spawn_console);
+    let mut last_command = None;
+    if options.debug {
+        println!("{}", debugger::HELP);
+        debugger.pause();
+    }
+
+    'running: loop {
+        // Keys don't press buttons while a movie plays.
+        let playing = movie.as_ref().is_some_and(|movie| movie.is_playing());
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    break 'running;
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => {
+                    let path = state_path(&options.rom_path, state_slot);
+                    match fs::write(&path, emulator.save_state()) {
+                        Ok(()) => println!("Saved state to slot {state_slot} ({path})"),
+                        Err(err) => eprintln!("Could not save state to {path}: {err}"),
+                    }
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9 | Keycode::Backspace),
+                    ..
+                } if movie.is_some() => {
+                    // The movie would no longer match what is on screen.
+                    eprintln!("Loading states and rewinding are disabled with --record and --play");
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    ..
+                } => {
+                    let path = state_path(&options.rom_path, state_slot);
+                    let loaded = fs::read(&path)
+                        .map_err(|err| err.to_string())
+                        .and_then(|state| {
+                            emulator.load_state(&state).map_err(|err| err.to_string())
+                        });
+
+                    match loaded {
+                        Ok(()) => {
+                            println!("Loaded state from slot {state_slot} ({path})");
+                            // A state saved before a fault gets the emulator going again.
+                            screen.set_halted(None);
+                        }
+                        Err(err) => eprintln!("Could not load state from {path}: {err}"),
+                    }
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    repeat,
+                    ..
+                } => {
+                    if options.rewind_seconds > 0 {
+                        rewinding = true;
+                    } else if !repeat {
+                        eprintln!("Rewinding is disabled with --rewind-seconds 0");
+                    }
+                }
+
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    ..
+                } => {
+                    if debugger.is_paused() {
+                        debugger.resume();
+                    } else {
+                        debugger.pause();
+                    }
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    ..
+                } => screen.show_registers = !screen.show_registers,
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    ..
+                } if debugger.is_paused() && !screen.halted => {
+                    if let Err(err) = debugger.step(emulator) {
+                        eprintln!("Emulation halted: {err}");
+                        screen.set_halted(Some(&err));
+                    }
+                }
+
+                Event::KeyDown {
+                    keycode: Some(key @ (Keycode::F6 | Keycode::F7)),
+                    ..
+                } => {
+                    state_slot = if key == Keycode::F6 {
+                        (state_slot + STATE_SLOTS - 1) % STATE_SLOTS
+                    } else {
+                        (state_slot + 1) % STATE_SLOTS
+                    };
+                    println!("Selected state slot {state_slot}");
+                }
+
+                Event::KeyDown {
+                    keycode, scancode, ..
+                } => {
+                    if let Some(btn) = keymap.button(keycode, scancode)
+                        && !playing
+                    {
+                        emulator.set_btn_press(btn, true);
+                    }
+                }
+
+                Event::KeyUp {
+                    keycode, scancode, ..
+                } => {
+                    if let Some(btn) = keymap.button(keycode, scancode)
+                        && !playing
+                    {
+                        emulator.set_btn_press(btn, false);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for line in console.iter().flat_map(|console| console.try_iter()) {
+            // An empty line repeats the last command, handy for stepping.
+            let command = if line.trim().is_empty() {
+                match last_command {
+                    Some(command) => command,
+                    None => continue,
+                }
+            } else {
+                match debugger.parse_command(&line) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        eprintln!("{err}");
+                        continue;
+                    }
+                }
+            };
+            last_command = Some(command);
+
+            match debugger.execute(command, emulator) {
+                Ok(output) => print!("{output}"),
+                Err(err) => {
+                    eprintln!("Emulation halted: {err}");
+                    screen.set_halted(Some(&err));
+                }
+            }
+        }
+
+        // Fetch, Decode, Execute Cycle
+
+        // Frames run at 60Hz whatever the refresh rate, so the window may show
+        // several of them at once, or the same one again.
+        let now = Instant::now();
+        let frames_due = scheduler.advance(now - last_update);
+        last_update = now;
+
+        for _ in 0..frames_due {
+            if rewinding {
+                // Go back one frame per frame due, so rewinding runs at real speed.
+                if let Some(state) = rewind.pop() {
+                    if let Err(err) = emulator.load_state(state) {
+                        eprintln!("Could not rewind: {err}");
+                    }
+                    screen.set_halted(None);
+                }
+                audio_device.pause();
+            } else if !screen.halted && !debugger.is_paused() {
+                if let Some(movie) = movie.as_deref_mut() {
+                    movie.poll(emulator);
+                }
+
+                match debugger.run_frame(
+                    emulator,
+                    scheduler.frame_instructions(),
+                    audio_device.as_mut(),
+                ) {
+                    Ok(StepOutcome::Exited) => break 'running,
+                    Ok(_) => rewind.push(emulator.save_state()),
+                    Err(err) => {
+                        // Keep the window open on the faulting frame so it can be looked at.
+                        eprintln!("Emulation halted: {err}");
+                        screen.set_halted(Some(&err));
+                        audio_device.pause();
+                    }
+                }
+                emulator.draw_flag = false;
+            }
+
+            frame_count += 1;
+        }
+
+        if let Some(stop) = debugger.take_stop() {
+            audio_device.pause();
+            print!("{}", debugger.report(&stop, emulator));
+        }
+        if debugger.is_paused() != screen.paused && !screen.halted {
+            screen.set_paused(debugger.is_paused());
+        }
+
+        screen.present(emulator);
+        emulator.draw_flag = false;
+
+        if options.frames.is_some_and(|frames| frame_count >= frames) {
+            break;
+        }
+    }
+
+    Ok(if screen.halted { cli::EXIT_FAULT } else { 0 })
+}
+
+const STATE_SLOTS: u8 = 10;
+const FRAMES_PER_SECOND: usize = 60;
+
+// Save states live next to the ROM, one file per slot: `pong.ch8.state0`, `pong.ch8.state1`...
+fn state_path(rom_path: &str, slot: u8) -> String {
+    format!("{rom_path}.state{slot}")
+}
+
+// Debugger commands typed on standard input. They are read on their own thread,
+// so that waiting for one never blocks the window.
+fn spawn_console() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for line in io::stdin().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+struct Screen {
+    canvas: Canvas<sdl2::video::Window>,
+    palette: [Color; 4],
+    scale: u32,
+    // Set once the emulator stops on an error, until a state is loaded.
+    halted: bool,
+    paused: bool,
+    // Toggled with F11.
+    show_registers: bool,
+}
+
+impl Screen {
+    fn set_halted(&mut self, error: Option<&EmulatorError>) {
+        self.halted = error.is_some();
+        self.set_status(error.map(|err| err.to_string()).as_deref());
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.set_status(paused.then_some("paused"));
+    }
+
+    // Shown in the window title, after the emulator name.
+    fn set_status(&mut self, status: Option<&str>) {
+        let title = match status {
+            Some(status) => format!("Chip-8 Emulator - {status}"),
+            None => String::from("Chip-8 Emulator"),
+        };
+        let _ = self.canvas.window_mut().set_title(&title);
+    }
+
+    // Red frame around the screen, shown once the emulator has stopped on an error.
+    fn draw_fault_overlay(&mut self) {
+        let (width, height) = (
+            SCREEN_WIDTH as u32 * self.scale,
+            SCREEN_HEIGHT as u32 * self.scale,
+        );
+        let thickness = 4;
+
+        self.canvas.set_draw_color(Color::RGB(200, 0, 0));
+        let _ = self.canvas.fill_rects(&[
+            Rect::new(0, 0, width, thickness),
+            Rect::new(0, (height - thickness) as i32, width, thickness),
+            Rect::new(0, 0, thickness, height),
+            Rect::new((width - thickness) as i32, 0, thickness, height),
+        ]);
+    }
+
+    // Panel in the top-left corner with the registers, timers and stack, redrawn
+    // with every frame so it follows the ROM as it runs.
+    fn draw_registers(&mut self, emulator: &Emulator) {
+        let (delay_timer, sound_timer) = emulator.timers();
+        let mut lines = vec![
+            format!(
+                "PC {:04X}  I {:04X}",
+                emulator.pc(),
+                emulator.index_register()
+            ),
+            format!("DT {delay_timer:02X}  ST {sound_timer:02X}"),
+        ];
+        for (row, values) in emulator.v_registers().chunks(4).enumerate() {
+            let registers: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X} {value:02X}", row * 4 + i))
+                .collect();
+            lines.push(registers.join(" "));
+        }
+        lines.push(String::from("STACK"));
+        for addresses in emulator.stack().chunks(4) {
+            let addresses: Vec<String> = addresses
+                .iter()
+                .map(|address| format!("{address:04X}"))
+                .collect();
+            lines.push(addresses.join(" "));
+        }
+
+        // Glyphs are 3x5 dots, with a dot of space between them.
+        let dot = (self.scale / 4).max(1) as i32;
+        let margin = 2 * dot;
+        let columns = lines.iter().map(String::len).max().unwrap_or(0) as i32;
+        let width = 2 * margin + columns * 4 * dot - dot;
+        let height = 2 * margin + lines.len() as i32 * 6 * dot - dot;
+
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 192));
+        let _ = self
+            .canvas
+            .fill_rect(Rect::new(0, 0, width as u32, height as u32));
+        self.canvas.set_blend_mode(BlendMode::None);
+
+        self.canvas.set_draw_color(Color::RGB(255, 255, 0));
+        let mut dots = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            let top = margin + row as i32 * 6 * dot;
+            for (column, c) in line.chars().enumerate() {
+                let left = margin + column as i32 * 4 * dot;
+                for (y, bits) in glyph(c).iter().enumerate() {
+                    for x in 0..3 {
+                        if bits & (0b100 >> x) != 0 {
+                            dots.push(Rect::new(
+                                left + x * dot,
+                                top + y as i32 * dot,
+                                dot as u32,
+                                dot as u32,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        let _ = self.canvas.fill_rects(&dots);
+    }
+}
+
+// The dots of the characters the register panel uses, one row per byte, the
+// leftmost dot in bit 2.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        _ => [0; 5],
+    }
+}
+
+impl DisplaySink for Screen {
+    fn present(&mut self, emulator: &Emulator) {
+        let (width, height) = emulator.screen_size();
+        // The window keeps its size, so low-res pixels are drawn twice as big as
+        // hi-res ones. Edges are worked out from the window size, so that pixels
+        // still fill it when the scale doesn't divide evenly.
+        let window_width = SCREEN_WIDTH as u32 * self.scale;
+        let window_height = SCREEN_HEIGHT as u32 * self.scale;
+        let edge = |i: usize, count: usize, size: u32| (i as u32 * size / count as u32) as i32;
+
+        for (y, line) in emulator.display.iter().take(height).enumerate() {
+            for (x, pixel) in line.iter().take(width).enumerate() {
+                // Pixels hold the bitmask of the XO-CHIP planes they are lit on.
+                self.canvas.set_draw_color(self.palette[*pixel as usize]);
+
+                let left = edge(x, width, window_width);
+                let top = edge(y, height, window_height);
+                let _ = self.canvas.fill_rect(Rect::new(
+                    left,
+                    top,
+                    (edge(x + 1, width, window_width) - left) as u32,
+                    (edge(y + 1, height, window_height) - top) as u32,
+                ));
+            }
+        }
+
+        if self.show_registers {
+            self.draw_registers(emulator);
+        }
+        if self.halted {
+            self.draw_fault_overlay();
+        }
+        self.canvas.present();
+    }
+}